@@ -0,0 +1,195 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Known lint problems that shouldn't be reported again, for `--baseline`.
+//!
+//! Problems are identified by their lint, their file, and a fingerprint of
+//! their title and snippets. Line numbers aren't part of the fingerprint, so
+//! problems stay known when unrelated edits move them around.
+
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use eipw_snippets::Message;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use snafu::{Backtrace, ResultExt, Snafu};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("i/o error accessing `{}`", path.to_string_lossy()))]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("unable to parse baseline `{}`", path.to_string_lossy()))]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("unable to serialize baseline `{}`", path.to_string_lossy()))]
+    Serialize {
+        path: PathBuf,
+        source: serde_json::Error,
+        backtrace: Backtrace,
+    },
+}
+
+/// One known problem.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Entry {
+    lint: Option<String>,
+
+    /// File the problem is in, relative to the repository.
+    file: Option<PathBuf>,
+
+    fingerprint: String,
+}
+
+impl Entry {
+    pub fn new(message: &Message<'_>, repo_dir: &Path) -> Self {
+        let file = message
+            .snippets
+            .iter()
+            .find_map(|s| s.origin.as_deref())
+            .map(|o| {
+                let origin = Path::new(o);
+                origin.strip_prefix(repo_dir).unwrap_or(origin).to_owned()
+            });
+
+        let mut hasher = Sha3_256::new();
+        hasher.update(message.title.as_bytes());
+        for snippet in &message.snippets {
+            hasher.update([0]);
+            hasher.update(snippet.source.as_bytes());
+        }
+
+        Self {
+            lint: message.id.as_deref().map(str::to_owned),
+            file,
+            fingerprint: format!("{:x}", hasher.finalize()),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct File {
+    problems: Vec<Entry>,
+}
+
+/// Known problems, each of which can be suppressed as many times as it was
+/// recorded.
+#[derive(Debug, Default)]
+pub struct Baseline {
+    remaining: HashMap<Entry, usize>,
+}
+
+impl Baseline {
+    /// Read the baseline at `path`, which is empty if `missing_ok` and the
+    /// file doesn't exist.
+    pub fn load(path: &Path, missing_ok: bool) -> Result<Self, Error> {
+        let bytes = match std::fs::read(path) {
+            Ok(b) => b,
+            Err(e) if missing_ok && e.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).context(IoSnafu { path }),
+        };
+        let file: File = serde_json::from_slice(&bytes).context(ParseSnafu { path })?;
+
+        let mut remaining = HashMap::new();
+        for entry in file.problems {
+            *remaining.entry(entry).or_default() += 1;
+        }
+
+        Ok(Self { remaining })
+    }
+
+    /// Whether `entry` is known (and shouldn't be reported.)
+    pub fn suppress(&mut self, entry: &Entry) -> bool {
+        match self.remaining.get_mut(entry) {
+            Some(n) if *n > 0 => {
+                *n -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Number of known problems in `files` that weren't seen again.
+    pub fn unmatched(&self, files: &[PathBuf]) -> usize {
+        self.remaining
+            .iter()
+            .filter(|(e, _)| e.file.as_ref().is_some_and(|f| files.contains(f)))
+            .map(|(_, n)| n)
+            .sum()
+    }
+
+    /// Replace the known problems in the linted `files` with `entries`, and
+    /// write the result to `path`. Problems in other files are kept.
+    pub fn write(
+        self,
+        path: &Path,
+        files: &[PathBuf],
+        mut entries: Vec<Entry>,
+    ) -> Result<(), Error> {
+        for (entry, n) in self.remaining {
+            let linted = entry.file.as_ref().is_some_and(|f| files.contains(f));
+            if !linted {
+                entries.extend(std::iter::repeat_n(entry, n));
+            }
+        }
+
+        entries.sort();
+        let file = File { problems: entries };
+        let json = serde_json::to_vec_pretty(&file).context(SerializeSnafu { path })?;
+        std::fs::write(path, json).context(IoSnafu { path })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eipw_snippets::{Level, Snippet};
+
+    fn message(source: &str, line_start: usize) -> Message<'_> {
+        Level::Error
+            .title("preamble header `title` is too long")
+            .id("preamble-len-title")
+            .snippet(
+                Snippet::source(source)
+                    .line_start(line_start)
+                    .origin("/repo/content/00001.md"),
+            )
+    }
+
+    #[test]
+    fn suppresses_known_problems() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("baseline.json");
+        let repo = Path::new("/repo");
+        let files = [PathBuf::from("content/00001.md")];
+
+        let known = Entry::new(&message("title: Long", 3), repo);
+        Baseline::load(&path, true)
+            .unwrap()
+            .write(&path, &files, vec![known])
+            .unwrap();
+
+        let mut baseline = Baseline::load(&path, false).unwrap();
+        assert_eq!(baseline.unmatched(&files), 1);
+        assert_eq!(baseline.unmatched(&[]), 0);
+
+        // Moving the problem to another line keeps it known, but only once.
+        assert!(baseline.suppress(&Entry::new(&message("title: Long", 7), repo)));
+        assert!(!baseline.suppress(&Entry::new(&message("title: Long", 7), repo)));
+        assert!(!baseline.suppress(&Entry::new(&message("title: Longer", 3), repo)));
+        assert_eq!(baseline.unmatched(&files), 0);
+    }
+}
@@ -28,13 +28,16 @@ pub enum Error {
 struct Inner {
     _lock: LockFile,
     dir: PathBuf,
+    offline: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct Cache(Arc<Inner>);
 
 impl Cache {
-    pub fn open() -> Result<Self, Error> {
+    /// Open (and lock) the cache. When `offline` is set, only entries that are
+    /// already present can be used.
+    pub fn open(offline: bool) -> Result<Self, Error> {
         debug!("opening local file cache");
 
         let dirs =
@@ -62,9 +65,14 @@ impl Cache {
         Ok(Self(Arc::new(Inner {
             _lock: lock,
             dir: cache_path.into(),
+            offline,
         })))
     }
 
+    pub fn offline(&self) -> bool {
+        self.0.offline
+    }
+
     pub fn dir(&self, key: &str) -> Result<PathBuf, Error> {
         let mut hasher = Sha3_256::new();
         hasher.update(key.as_bytes());
@@ -15,7 +15,12 @@ use snafu::{ResultExt, Whatever};
 
 use crate::{cli::ChangedFormat, config::RepositoryUse, git, layout::REPO_DIR};
 
-pub(crate) fn is_proposal_path(mut p: PathBuf) -> bool {
+pub(crate) fn is_proposal_path(p: PathBuf) -> bool {
+    proposal_number(p).is_some()
+}
+
+/// Number of the proposal at `p` (relative to the repository), if it is one.
+pub(crate) fn proposal_number(mut p: PathBuf) -> Option<u64> {
     // Only lint `content/00001.md` and `content/00001/index.md` files.
 
     // content/00000.md  |  content/00000/index.md
@@ -27,18 +32,13 @@ pub(crate) fn is_proposal_path(mut p: PathBuf) -> bool {
         Some(_) if p.extension().map(|x| x == "md").unwrap_or(false) => {
             p.set_extension("");
         }
-        None | Some(_) => return false,
+        None | Some(_) => return None,
     }
 
     // content/00000
     //         ^^^^^
-    match p.file_name().and_then(OsStr::to_str) {
-        None => return false,
-        Some(f) if f.parse::<u64>().is_err() => return false,
-        Some(_) => {
-            p.pop();
-        }
-    }
+    let number = p.file_name().and_then(OsStr::to_str)?.parse::<u64>().ok()?;
+    p.pop();
 
     // content
     // ^^^^^^^
@@ -46,22 +46,27 @@ pub(crate) fn is_proposal_path(mut p: PathBuf) -> bool {
         Some(f) if f == "content" => {
             p.pop();
         }
-        _ => return false,
+        _ => return None,
     }
 
-    p == OsStr::new("")
+    if p == OsStr::new("") {
+        Some(number)
+    } else {
+        None
+    }
 }
 
 pub(crate) fn run(
     root_path: &Path,
     build_path: &Path,
     repo_use: RepositoryUse,
+    git_options: git::Options,
     all: bool,
     format: &ChangedFormat,
 ) -> Result<(), Whatever> {
     let repo_path = build_path.join(REPO_DIR);
 
-    let both = git::Fresh::new(root_path, &repo_path, repo_use)
+    let both = git::Fresh::new(root_path, &repo_path, repo_use, git_options)
         .whatever_context("initializing build repo")?
         .clone_src()
         .whatever_context("cloning source repo")?
@@ -10,7 +10,7 @@ use std::path::{Path, PathBuf};
 
 use clap::{Parser, Subcommand};
 
-use crate::{lint, print};
+use crate::{lint, new, print};
 
 /// Build script for Ethereum EIPs and ERCs.
 #[derive(Parser, Debug)]
@@ -20,6 +20,14 @@ pub(crate) struct Args {
     #[clap(short = 'C')]
     pub(crate) root: Option<PathBuf>,
 
+    /// Build from the working tree, including uncommitted changes, instead of the last commit
+    #[clap(long, global = true)]
+    pub(crate) worktree: bool,
+
+    /// Reuse previously fetched repositories instead of accessing the network
+    #[clap(long, global = true)]
+    pub(crate) offline: bool,
+
     #[clap(subcommand)]
     pub(crate) operation: Operation,
 }
@@ -44,6 +52,69 @@ pub(crate) enum Operation {
         eipw: lint::CmdArgs,
     },
 
+    /// Lint proposals in the working tree, without building (or needing zola)
+    Lint {
+        /// Read eipw configuration from CONFIG instead of the theme
+        #[clap(long)]
+        config: Option<PathBuf>,
+
+        #[command(flatten)]
+        eipw: lint::CmdArgs,
+    },
+
+    /// Fix problems that lints can fix automatically, in the working tree
+    Fix {
+        /// Show what would change, without changing anything
+        #[clap(long)]
+        dry_run: bool,
+
+        /// Read eipw configuration from CONFIG instead of the theme
+        #[clap(long)]
+        config: Option<PathBuf>,
+
+        #[command(flatten)]
+        eipw: lint::SourceArgs,
+    },
+
+    /// Format proposals in the working tree in a canonical style
+    Fmt {
+        /// Don't change anything, but fail if any proposal isn't formatted
+        #[clap(long)]
+        check: bool,
+
+        /// Read eipw configuration from CONFIG instead of the theme
+        #[clap(long)]
+        config: Option<PathBuf>,
+
+        #[command(flatten)]
+        eipw: lint::SourceArgs,
+    },
+
+    /// Create a new proposal from the template, then lint it
+    New {
+        #[command(flatten)]
+        new: new::CmdArgs,
+
+        /// Read eipw configuration from CONFIG instead of the theme
+        #[clap(long)]
+        config: Option<PathBuf>,
+
+        #[command(flatten)]
+        eipw: lint::CmdArgs,
+    },
+
+    /// Move a proposal to a new number, updating `requires` and links to it
+    Renumber {
+        /// Number of the proposal to move
+        old: u64,
+
+        /// Number to move it to
+        new: u64,
+    },
+
+    /// Move proposals from the old Jekyll layout (`EIPS/eip-1234.md`) into `content`
+    Migrate,
+
     /// Remove temporary and output files
     Clean,
 
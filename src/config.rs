@@ -4,7 +4,12 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use std::{borrow::Borrow, collections::HashMap, path::PathBuf, str::FromStr};
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    str::FromStr,
+};
 
 use regex::Regex;
 use serde::{Deserialize, Serialize};
@@ -143,7 +148,11 @@ impl std::fmt::Display for LocName {
 
 pub type Locations = HashMap<LocName, Location>;
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Lint configuration for individual proposals, keyed by proposal number, in
+/// the same format as eipw's own configuration.
+pub type LintOverrides = BTreeMap<String, toml::Table>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 struct InnerManifest {
     name: LocName,
@@ -152,14 +161,18 @@ struct InnerManifest {
     locations: Locations,
 
     theme: Theme,
+
+    #[serde(default, skip_serializing_if = "LintOverrides::is_empty")]
+    lint_overrides: LintOverrides,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Manifest {
     pub manifest_path: PathBuf,
     pub name: LocName,
     pub locations: Locations,
     pub theme: Theme,
+    pub lint_overrides: LintOverrides,
 }
 
 impl Manifest {
@@ -183,6 +196,7 @@ impl Manifest {
             name: inner.name,
             locations: inner.locations,
             theme: inner.theme,
+            lint_overrides: inner.lint_overrides,
         })
     }
 
@@ -306,6 +320,33 @@ commit = "aaa"
         assert_eq!(core.base_url.as_str(), "https://example.test/EIPs/");
     }
 
+    #[test]
+    fn parses_lint_overrides() {
+        let repo = TestRepo::new();
+        let manifest_path = repo.write_file(
+            MANIFEST_FILE,
+            r#"
+name = "Core"
+
+[locations.Core]
+repository = "https://example.test/EIPs.git"
+base-url = "https://example.test/EIPs/"
+
+[theme]
+repository = "https://example.test/theme.git"
+commit = "aaa"
+
+[lint-overrides.1234.lints.preamble-author]
+enabled = false
+"#,
+        );
+
+        let manifest = Manifest::load(&manifest_path).expect("loaded successfully");
+
+        let lints = manifest.lint_overrides["1234"]["lints"].as_table().unwrap();
+        assert_eq!(lints["preamble-author"]["enabled"].as_bool(), Some(false));
+    }
+
     #[test]
     fn repo_manifest_rejects_unsafe_names() {
         let repo = TestRepo::new();
@@ -4,14 +4,14 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use snafu::{ResultExt, Whatever};
 
-use crate::{cli::Args, find_root};
+use crate::find_root;
 
-pub(crate) fn root(args: &Args) -> Result<PathBuf, Whatever> {
-    let dir = match &args.root {
+pub(crate) fn root(root: Option<&Path>) -> Result<PathBuf, Whatever> {
+    let dir = match root {
         None => find_root::find_root().whatever_context("cannot find root")?,
         Some(p) => p.to_path_buf(),
     };
@@ -0,0 +1,326 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Rewriting proposals to fix problems found by the enabled lints, for
+//! `build-eips fix`.
+//!
+//! Only problems with one obviously right answer are fixed: field order,
+//! whitespace around values, date formats, author lists, the spacing and
+//! order of lists, and links to proposals on the website (which become
+//! relative links.)
+
+use std::path::Path;
+
+use chrono::NaiveDate;
+use eipw_lint::lints::DefaultLint;
+use eipw_preamble::Preamble;
+use log::info;
+use regex::{Regex, RegexSet};
+use snafu::{ResultExt, Whatever};
+
+use crate::{interrupt, layout::CONTENT_DIR, lint, markdown, migrate};
+
+/// Formats `created` and friends are fixed from.
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%Y/%m/%d", "%Y.%m.%d"];
+
+/// Links to a proposal (and maybe a heading in it) on the website.
+const PROPOSAL_URL: &str =
+    r"^(?:https?:)?//(?:eips|ercs)\.ethereum\.org/(?:EIPS|ERCS)/(?:eip|erc)-(\d+)(?:\.md)?(#.*)?$";
+
+/// Fixes for the problems that some lints find.
+#[derive(Debug, Default)]
+pub struct Fixer {
+    order: Vec<String>,
+    trim: bool,
+    authors: Vec<String>,
+    dates: Vec<String>,
+    lists: Vec<String>,
+    uint_lists: Vec<String>,
+
+    /// Exceptions to `markdown-rel-links`, if it's enabled.
+    rel_links: Option<RegexSet>,
+}
+
+impl Fixer {
+    /// Fix the problems found by the given `lints`.
+    pub fn new(lints: Vec<DefaultLint<String>>) -> Self {
+        let mut fixer = Self::default();
+
+        for lint in lints {
+            match lint {
+                DefaultLint::PreambleOrder { names } => fixer.order = names.0,
+                DefaultLint::PreambleTrim(_) => fixer.trim = true,
+                DefaultLint::PreambleAuthor { name } => fixer.authors.push(name.0),
+                DefaultLint::PreambleDate { name } => fixer.dates.push(name.0),
+                DefaultLint::PreambleList { name } => fixer.lists.push(name.0),
+                DefaultLint::PreambleUintList { name } => fixer.uint_lists.push(name.0),
+                // Links are left alone if the exceptions don't parse.
+                DefaultLint::MarkdownRelativeLinks(l) => {
+                    fixer.rel_links = RegexSet::new(l.exceptions).ok();
+                }
+                _ => (),
+            }
+        }
+
+        fixer
+    }
+
+    fn fix_date(value: &str) -> Option<String> {
+        DATE_FORMATS
+            .iter()
+            .find_map(|f| NaiveDate::parse_from_str(value, f).ok())
+            .map(|d| d.format("%Y-%m-%d").to_string())
+    }
+
+    fn fix_authors(value: &str) -> Option<String> {
+        let authors = markdown::extract_authors(value).ok()?;
+        let authors: Vec<_> = authors.iter().map(ToString::to_string).collect();
+        Some(authors.join(", "))
+    }
+
+    fn fix_list(value: &str) -> Option<String> {
+        let items: Vec<_> = value.split(',').map(str::trim).collect();
+        if items.iter().any(|i| i.is_empty()) {
+            return None;
+        }
+        Some(items.join(", "))
+    }
+
+    fn fix_uint_list(value: &str) -> Option<String> {
+        let mut items = value
+            .split(',')
+            .map(|i| i.trim().parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?;
+        items.sort_unstable();
+        items.dedup();
+
+        let items: Vec<_> = items.iter().map(u64::to_string).collect();
+        Some(items.join(", "))
+    }
+
+    /// Value of the field called `name` once fixed, if it needs fixing.
+    fn fix_value(&self, name: &str, value: &str) -> Option<String> {
+        let trimmed = value.trim();
+        let is = |names: &[String]| names.iter().any(|n| n == name);
+
+        let fixed = if trimmed.is_empty() {
+            None
+        } else if is(&self.uint_lists) {
+            Self::fix_uint_list(trimmed)
+        } else if is(&self.authors) {
+            Self::fix_authors(trimmed)
+        } else if is(&self.lists) {
+            Self::fix_list(trimmed)
+        } else if is(&self.dates) {
+            Self::fix_date(trimmed)
+        } else {
+            None
+        };
+
+        match fixed {
+            Some(f) => Some(f),
+            None if self.trim => Some(trimmed.to_owned()),
+            None => None,
+        }
+    }
+
+    /// Fix the fields of a preamble (without its `---` delimiters.)
+    pub fn fix_preamble(&self, preamble: &str) -> Option<String> {
+        let fields: Vec<_> = Preamble::parse(None, preamble).ok()?.fields().collect();
+
+        let mut lines: Vec<_> = fields
+            .iter()
+            .map(|field| match self.fix_value(field.name(), field.value()) {
+                Some(value) if value.is_empty() => format!("{}:", field.name()),
+                Some(value) => format!("{}: {value}", field.name()),
+                None => field.source().to_owned(),
+            })
+            .zip(&fields)
+            .collect();
+
+        if !self.order.is_empty() {
+            // Fields missing from the order (which other lints complain about)
+            // go at the end, in their original order.
+            lines.sort_by_key(|(_, field)| {
+                self.order
+                    .iter()
+                    .position(|n| n == field.name())
+                    .unwrap_or(self.order.len())
+            });
+        }
+
+        let lines: Vec<_> = lines.into_iter().map(|(line, _)| line).collect();
+        Some(lines.join("\n"))
+    }
+
+    /// Fix the preamble of a proposal, returning its new contents. Proposals
+    /// without a valid preamble are left alone.
+    pub fn fix(&self, contents: &str) -> Option<String> {
+        let (preamble, _) = Preamble::split(contents).ok()?;
+        let fixed = self.fix_preamble(preamble)?;
+
+        // Splice the new preamble in place of the old one, leaving the
+        // delimiters and body exactly as they were.
+        let start = preamble.as_ptr() as usize - contents.as_ptr() as usize;
+        let end = start + preamble.len();
+
+        Some(format!("{}{fixed}{}", &contents[..start], &contents[end..]))
+    }
+
+    /// Replace links to proposals on the website with relative links, in a
+    /// proposal in `dir` (relative to the content directory.)
+    pub fn fix_links(&self, dir: &Path, contents: &str) -> String {
+        let mut output = contents.to_owned();
+        let Some(exceptions) = &self.rel_links else {
+            return output;
+        };
+
+        let re = Regex::new(PROPOSAL_URL).unwrap();
+        for (range, dest) in markdown::link_destinations(contents).into_iter().rev() {
+            if exceptions.is_match(&dest) {
+                continue;
+            }
+            let Some(captures) = re.captures(&dest) else {
+                continue;
+            };
+            let Ok(number) = captures[1].parse::<u64>() else {
+                continue;
+            };
+
+            let mut link = migrate::relative(dir, Path::new(&format!("{number:05}.md")));
+            if let Some(fragment) = captures.get(2) {
+                link.push_str(fragment.as_str());
+            }
+            output.replace_range(range, &link);
+        }
+        output
+    }
+}
+
+/// Fix what can be fixed in the proposals selected by `eipw` in the root
+/// checkout, then lint what's left. With `dry_run`, print the fixes as a diff
+/// instead.
+pub fn run(
+    settings: &lint::Settings,
+    root_path: &Path,
+    eipw: lint::CmdArgs,
+    dry_run: bool,
+) -> Result<(), Whatever> {
+    eipw.require_sources("fix")?;
+
+    let sources = lint::sources(settings, root_path, eipw.clone())
+        .whatever_context("unable to find proposals to fix")?;
+
+    // Sources are canonical paths, so the content directory has to be too.
+    let content_path = root_path.join(CONTENT_DIR).canonicalize().ok();
+
+    let mut n_fixed = 0;
+    for lint::Source {
+        path: source,
+        lints,
+    } in sources
+    {
+        interrupt::check().whatever_context("fixing stopped")?;
+        let contents = std::fs::read_to_string(&source)
+            .with_whatever_context(|_| format!("unable to read `{}`", source.display()))?;
+
+        let fixer = Fixer::new(lints);
+        let mut fixed = fixer.fix(&contents).unwrap_or_else(|| contents.clone());
+        let dir = content_path
+            .as_deref()
+            .and_then(|c| source.parent()?.strip_prefix(c).ok());
+        if let Some(dir) = dir {
+            fixed = fixer.fix_links(dir, &fixed);
+        }
+
+        if fixed == contents {
+            continue;
+        }
+        n_fixed += 1;
+
+        if !dry_run {
+            std::fs::write(&source, fixed)
+                .with_whatever_context(|_| format!("unable to write `{}`", source.display()))?;
+            continue;
+        }
+
+        let relative = source
+            .strip_prefix(root_path)
+            .unwrap_or(&source)
+            .to_string_lossy();
+        let diff = similar::TextDiff::from_lines(&contents, &fixed);
+        print!(
+            "{}",
+            diff.unified_diff()
+                .header(&format!("a/{relative}"), &format!("b/{relative}"))
+        );
+    }
+
+    if dry_run {
+        info!("{n_fixed} file(s) would be fixed");
+        return Ok(());
+    }
+
+    info!("fixed {n_fixed} file(s)");
+    lint::checkout(settings, root_path, eipw).whatever_context("linting failed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eipw_lint::lints::preamble;
+
+    #[test]
+    fn fixes_preamble() {
+        let fixer = Fixer::new(vec![
+            DefaultLint::PreambleOrder {
+                names: preamble::Order(vec!["eip".into(), "created".into(), "requires".into()]),
+            },
+            DefaultLint::PreambleTrim(preamble::Trim),
+            DefaultLint::PreambleDate {
+                name: preamble::Date("created".into()),
+            },
+            DefaultLint::PreambleUintList {
+                name: preamble::UintList("requires".into()),
+            },
+            DefaultLint::PreambleAuthor {
+                name: preamble::Author("author".into()),
+            },
+        ]);
+
+        let contents =
+            "---\nrequires: 20,1 , 20\nextra:  value \nauthor: A (@a) ,B <b@example.com>\ncreated: 2024/1/5\neip:1\n---\n\nBody\n";
+        assert_eq!(
+            fixer.fix(contents).unwrap(),
+            "---\neip: 1\ncreated: 2024-01-05\nrequires: 1, 20\nextra: value\nauthor: A (@a), B <b@example.com>\n---\n\nBody\n"
+        );
+
+        assert_eq!(Fixer::default().fix(contents).unwrap(), contents);
+        assert_eq!(fixer.fix("no preamble"), None);
+    }
+
+    #[test]
+    fn fixes_links() {
+        let fixer = Fixer::new(vec![DefaultLint::MarkdownRelativeLinks(
+            eipw_lint::lints::markdown::RelativeLinks {
+                exceptions: vec!["^https://eips\\.ethereum\\.org/EIPS/eip-2$".into()],
+            },
+        )]);
+
+        let contents = "---\neip: 1\n---\n\n\
+            See [one](https://eips.ethereum.org/EIPS/eip-20#spec), \
+            [two](https://eips.ethereum.org/EIPS/eip-2), and \
+            [three](https://example.com/eip-20).\n";
+        assert_eq!(
+            fixer.fix_links(Path::new("00001"), contents),
+            "---\neip: 1\n---\n\n\
+            See [one](../00020.md#spec), \
+            [two](https://eips.ethereum.org/EIPS/eip-2), and \
+            [three](https://example.com/eip-20).\n"
+        );
+    }
+}
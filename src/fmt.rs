@@ -0,0 +1,214 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Formatting proposals in a canonical style, for `build-eips fmt`.
+//!
+//! Preambles are fixed the way `build-eips fix` fixes them, and bodies are
+//! round-tripped through the markdown parser. Tables keep their source, since
+//! round-tripping them would drop their padding.
+
+use std::path::Path;
+
+use eipw_preamble::Preamble;
+use log::{info, warn};
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use pulldown_cmark_to_cmark::{
+    calculate_code_block_token_count, cmark_resume_with_options, Options,
+};
+use snafu::{whatever, ResultExt, Whatever};
+
+use crate::{fix::Fixer, lint, markdown};
+
+/// Round-trip `body` through the markdown parser.
+fn format_body(body: &str) -> Option<String> {
+    let events: Vec<_> = Parser::new_ext(body, markdown::parser_options())
+        .into_offset_iter()
+        .collect();
+
+    let options = Options {
+        code_block_token_count: calculate_code_block_token_count(events.iter().map(|(e, _)| e))
+            .unwrap_or(3),
+        ..Default::default()
+    };
+
+    let mut output = String::with_capacity(body.len());
+    let mut state = None;
+    let mut pending = Vec::new();
+    let mut events = events.into_iter();
+
+    while let Some((event, range)) = events.next() {
+        if !matches!(event, Event::Start(Tag::Table(_))) {
+            pending.push(event);
+            continue;
+        }
+
+        let mut resumed =
+            cmark_resume_with_options(pending.drain(..), &mut output, state, options.clone())
+                .ok()?;
+
+        // Tables nested in lists and quotes need their prefixes, so only
+        // top-level tables are copied as-is.
+        if !resumed.padding.is_empty() {
+            pending.push(event);
+            state = Some(resumed);
+            continue;
+        }
+
+        for (event, _) in events.by_ref() {
+            if matches!(event, Event::End(TagEnd::Table)) {
+                break;
+            }
+        }
+
+        if !output.is_empty() {
+            output.push_str(&"\n".repeat(resumed.newlines_before_start));
+        }
+        output.push_str(body[range].trim_end());
+        resumed.newlines_before_start = options.newlines_after_table;
+        state = Some(resumed);
+    }
+
+    cmark_resume_with_options(pending.into_iter(), &mut output, state, options)
+        .ok()?
+        .finalize(&mut output)
+        .ok()?;
+
+    Some(output)
+}
+
+/// Format a proposal, returning its new contents. Proposals without a valid
+/// preamble can't be formatted.
+fn format(fixer: &Fixer, contents: &str) -> Option<String> {
+    let (preamble, body) = Preamble::split(contents).ok()?;
+    let preamble = fixer.fix_preamble(preamble)?;
+    let body = format_body(body)?;
+
+    Some(format!("---\n{preamble}\n---\n\n{}\n", body.trim()))
+}
+
+/// Format the proposals selected by `eipw`, fixing preambles with the lints
+/// enabled for each (including overrides.)
+///
+/// With `check`, nothing changes, and the paths of proposals that aren't
+/// formatted (or can't be, because of an invalid preamble) are printed.
+pub(crate) fn run(
+    root_path: &Path,
+    settings: &lint::Settings,
+    eipw: lint::CmdArgs,
+    check: bool,
+) -> Result<(), Whatever> {
+    eipw.require_sources("format")?;
+
+    let sources =
+        lint::sources(settings, root_path, eipw).whatever_context("unable to find proposals")?;
+
+    let mut unformatted = 0;
+    let mut invalid = 0;
+    for lint::Source {
+        path: source,
+        lints,
+    } in sources
+    {
+        let contents = std::fs::read_to_string(&source)
+            .with_whatever_context(|_| format!("unable to read `{}`", source.display()))?;
+        let relative = source.strip_prefix(root_path).unwrap_or(&source);
+
+        let formatted = match format(&Fixer::new(lints), &contents) {
+            Some(f) => f,
+            None => {
+                warn!("not formatting `{}` (invalid preamble)", source.display());
+                if check {
+                    println!("{}", relative.display());
+                }
+                invalid += 1;
+                continue;
+            }
+        };
+
+        if formatted == contents {
+            continue;
+        }
+        unformatted += 1;
+
+        if check {
+            println!("{}", relative.display());
+        } else {
+            std::fs::write(&source, formatted)
+                .with_whatever_context(|_| format!("unable to write `{}`", source.display()))?;
+        }
+    }
+
+    if !check {
+        info!("formatted {unformatted} file(s)");
+    } else if invalid > 0 {
+        whatever!(
+            "{unformatted} file(s) need formatting, and {invalid} can't be formatted \
+            (invalid preamble)"
+        );
+    } else if unformatted > 0 {
+        whatever!("{unformatted} file(s) need formatting (run `build-eips fmt`)");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use eipw_lint::config::DefaultOptions;
+
+    use super::*;
+
+    fn fixer() -> Fixer {
+        let lints = DefaultOptions::<String>::default()
+            .lints
+            .into_values()
+            .filter_map(|l| l.into_lint())
+            .collect();
+        Fixer::new(lints)
+    }
+
+    #[test]
+    fn formats_proposal() {
+        let contents = "---\n\
+            requires: 20,1\n\
+            title:  Example \n\
+            author: Alice (@alice) ,Bob <bob@example.com>\n\
+            eip: 1\n\
+            ---\n\
+            Heading\n\
+            =======\n\n\
+            Some *text*.\n\n\
+            | Name  | Value |\n\
+            |-------|-------|\n\
+            | a     | 1     |\n\n\
+            ```rust\n\
+            let x = 1;\n\
+            ```\n\n\n";
+
+        let fixer = fixer();
+        let formatted = format(&fixer, contents).unwrap();
+        assert_eq!(
+            formatted,
+            "---\n\
+            eip: 1\n\
+            title: Example\n\
+            author: Alice (@alice), Bob <bob@example.com>\n\
+            requires: 1, 20\n\
+            ---\n\n\
+            # Heading\n\n\
+            Some *text*.\n\n\
+            | Name  | Value |\n\
+            |-------|-------|\n\
+            | a     | 1     |\n\n\
+            ```rust\n\
+            let x = 1;\n\
+            ```\n"
+        );
+
+        // Formatted proposals stay formatted.
+        assert_eq!(format(&fixer, &formatted).unwrap(), formatted);
+    }
+}
@@ -5,7 +5,11 @@
  */
 
 use std::{
+    collections::{HashMap, HashSet},
     ffi::OsStr,
+    fs::Metadata,
+    io::ErrorKind,
+    ops::Range,
     path::{absolute, Path, PathBuf},
 };
 
@@ -16,8 +20,8 @@ use crate::{
 };
 use git2::{
     build::{CheckoutBuilder, TreeUpdateBuilder},
-    BranchType, Commit, FetchOptions, FileMode, ObjectType, Oid, RepositoryOpenFlags, Signature,
-    StatusOptions, Tree, TreeEntry, TreeWalkResult,
+    Commit, Diff, DiffOptions, FetchOptions, FileMode, ObjectType, Oid, RepositoryOpenFlags,
+    Signature, Sort, StatusOptions, Tree, TreeEntry, TreeWalkResult,
 };
 use log::{debug, info};
 use snafu::{ensure, Backtrace, IntoError, OptionExt, ResultExt, Snafu};
@@ -48,6 +52,8 @@ pub enum Error {
     Dirty { backtrace: Backtrace },
     #[snafu(display("unable to update tree ({msg})"))]
     UpdateTree { msg: String, backtrace: Backtrace },
+    #[snafu(display("{what} is not available offline"))]
+    Offline { what: String, backtrace: Backtrace },
     #[snafu(context(false))]
     Cache {
         #[snafu(backtrace)]
@@ -55,6 +61,19 @@ pub enum Error {
     },
 }
 
+/// Knobs controlling how the build repository is assembled.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    /// Build from the root repository's index and working tree, including
+    /// uncommitted and untracked files, instead of refusing to build when the
+    /// root repository is dirty.
+    pub worktree: bool,
+
+    /// Reuse the upstream and sibling repositories fetched by a previous run
+    /// instead of fetching them again.
+    pub offline: bool,
+}
+
 pub fn check_dirty(root_path: &Path) -> Result<(), Error> {
     let repo = git2::Repository::open(root_path).context(GitSnafu {
         what: "open root repository",
@@ -106,9 +125,26 @@ fn check_conflict(master_tree: &Tree, path: &Path, entry: &TreeEntry) -> Result<
     Ok(())
 }
 
+fn file_mode(metadata: &Metadata) -> FileMode {
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 != 0 {
+            return FileMode::BlobExecutable;
+        }
+    }
+
+    #[cfg(not(target_family = "unix"))]
+    let _ = metadata;
+
+    FileMode::Blob
+}
+
 pub struct Fresh {
     src_repo_use: RepositoryUse,
     src_repo_url: Url,
+    root_path: PathBuf,
+    options: Options,
 
     working_repo: git2::Repository,
 }
@@ -118,12 +154,15 @@ impl Fresh {
         root_path: &Path,
         build_path: &Path,
         src_repo_use: RepositoryUse,
+        options: Options,
     ) -> Result<Self, Error> {
         let root_path = absolute(root_path).context(IoSnafu { path: root_path })?;
-        check_dirty(&root_path)?;
+        if !options.worktree {
+            check_dirty(&root_path)?;
+        }
         let src_repo_url = Url::from_directory_path(&root_path)
             .ok()
-            .context(PathUrlSnafu { path: root_path })?;
+            .context(PathUrlSnafu { path: &root_path })?;
 
         debug!("source repository at `{src_repo_url}`");
 
@@ -133,12 +172,113 @@ impl Fresh {
             working_repo,
             src_repo_url,
             src_repo_use,
+            root_path,
+            options,
+        })
+    }
+
+    /// Record the root repository's working tree (uncommitted and untracked
+    /// files included) as a commit on top of `parent` in the build repository.
+    fn snapshot_worktree<'a>(&'a self, parent: Commit<'a>) -> Result<Commit<'a>, Error> {
+        let root_repo = git2::Repository::open(&self.root_path).context(GitSnafu {
+            what: "open root repository",
+        })?;
+        let mut options = StatusOptions::default();
+        options.include_untracked(true).recurse_untracked_dirs(true);
+        let statuses = root_repo.statuses(Some(&mut options)).context(GitSnafu {
+            what: "get root repository status",
+        })?;
+
+        let mut tree_builder = TreeUpdateBuilder::new();
+        let mut n_changed = 0;
+
+        for status in statuses.iter() {
+            let path = match status.path() {
+                Some(p) => p,
+                None => {
+                    return UpdateTreeSnafu {
+                        msg: "non-UTF-8 path in working tree",
+                    }
+                    .fail()
+                }
+            };
+
+            if Path::new(path).starts_with(super::BUILD_DIR) {
+                continue;
+            }
+
+            let full_path = self.root_path.join(path);
+            let metadata = match std::fs::symlink_metadata(&full_path) {
+                Ok(m) => m,
+                Err(e) if e.kind() == ErrorKind::NotFound => {
+                    debug!("snapshot remove `{path}`");
+                    tree_builder.remove(path);
+                    n_changed += 1;
+                    continue;
+                }
+                Err(e) => return Err(IoSnafu { path: full_path }.into_error(e)),
+            };
+
+            let (contents, mode) = if metadata.is_symlink() {
+                let target =
+                    std::fs::read_link(&full_path).context(IoSnafu { path: &full_path })?;
+                let target = target.to_str().with_context(|| UpdateTreeSnafu {
+                    msg: format!("non-UTF-8 symlink target for `{path}`"),
+                })?;
+                (target.as_bytes().to_vec(), FileMode::Link)
+            } else if metadata.is_file() {
+                let contents = std::fs::read(&full_path).context(IoSnafu { path: &full_path })?;
+                (contents, file_mode(&metadata))
+            } else {
+                debug!("snapshot skipping `{path}` (not a file)");
+                continue;
+            };
+
+            let blob = self
+                .working_repo
+                .blob(&contents)
+                .context(GitSnafu { what: "write blob" })?;
+
+            debug!("snapshot upsert `{path}`");
+            tree_builder.upsert(path, blob, mode);
+            n_changed += 1;
+        }
+
+        if n_changed == 0 {
+            debug!("working tree is clean");
+            return Ok(parent);
+        }
+
+        info!("including {n_changed} uncommitted file(s)");
+
+        let parent_tree = parent.tree().context(GitSnafu {
+            what: "getting head tree",
+        })?;
+        let tree_oid = tree_builder
+            .create_updated(&self.working_repo, &parent_tree)
+            .context(GitSnafu { what: "build tree" })?;
+        let tree = self.working_repo.find_tree(tree_oid).unwrap();
+
+        let sig =
+            Signature::now("eips-build", "eips-build@eips-build.invalid").context(GitSnafu {
+                what: "commit signature",
+            })?;
+        let oid = self
+            .working_repo
+            .commit(None, &sig, &sig, "Uncommitted changes", &tree, &[&parent])
+            .context(GitSnafu { what: "committing" })?;
+
+        self.working_repo.find_commit(oid).context(GitSnafu {
+            what: "find snapshot commit",
         })
     }
 
     pub fn clone_src(self) -> Result<SourceOnly, Error> {
         info!("cloning local repository");
-        let master = fetch(&self.working_repo, self.src_repo_url.as_str(), "HEAD")?;
+        let mut master = fetch(&self.working_repo, self.src_repo_url.as_str(), "HEAD")?;
+        if self.options.worktree {
+            master = self.snapshot_worktree(master)?;
+        }
         self.working_repo
             .set_head_detached(master.id())
             .context(GitSnafu { what: "detach" })?;
@@ -173,6 +313,7 @@ impl Fresh {
         Ok(SourceOnly {
             local_head,
             src_repo_use: self.src_repo_use,
+            options: self.options,
             working_repo: self.working_repo,
         })
     }
@@ -180,6 +321,7 @@ impl Fresh {
 
 pub struct SourceOnly {
     src_repo_use: RepositoryUse,
+    options: Options,
 
     working_repo: git2::Repository,
     local_head: Oid,
@@ -187,25 +329,62 @@ pub struct SourceOnly {
 
 impl SourceOnly {
     pub fn fetch_upstream(self) -> Result<SourceWithUpstream, Error> {
-        info!("fetching latest {} repository", self.src_repo_use.title);
-        let latest_master = fetch(
-            &self.working_repo,
-            self.src_repo_use.location.repository.as_str(),
-            "master",
-        )?;
+        let title = &self.src_repo_use.title;
+        let latest_master = if self.options.offline {
+            info!("using previously fetched {title} repository");
+            find_fetched(&self.working_repo, title)?
+        } else {
+            info!("fetching latest {title} repository");
+            fetch(
+                &self.working_repo,
+                self.src_repo_use.location.repository.as_str(),
+                &fetched_refspec(title),
+            )?
+        };
         let upstream_head = latest_master.id();
         drop(latest_master);
         Ok(SourceWithUpstream {
             upstream_head,
             local_head: self.local_head,
             src_repo_use: self.src_repo_use,
+            options: self.options,
             working_repo: self.working_repo,
         })
     }
 }
 
+/// Lines changed in each file, relative to the root of the repository.
+#[derive(Debug, Clone, Default)]
+pub struct ChangedLines {
+    lines: HashMap<PathBuf, Vec<Range<usize>>>,
+}
+
+impl ChangedLines {
+    /// Whether any of `lines` (counting from one, end exclusive) in `path`
+    /// changed.
+    pub fn touches(&self, path: &Path, lines: Range<usize>) -> bool {
+        self.lines
+            .get(path)
+            .map(|changed| {
+                changed
+                    .iter()
+                    .any(|c| c.start < lines.end && lines.start < c.end)
+            })
+            .unwrap_or(false)
+    }
+}
+
+/// A sibling repository merged into the build repository.
+#[derive(Debug, Clone)]
+pub struct Merged {
+    pub name: String,
+    pub repository: Url,
+    pub commit: Oid,
+}
+
 pub struct SourceWithUpstream {
     src_repo_use: RepositoryUse,
+    options: Options,
 
     working_repo: git2::Repository,
     local_head: Oid,
@@ -227,7 +406,17 @@ impl SourceWithUpstream {
         Ok(master_tree)
     }
 
-    pub fn changed_files(&self) -> Result<Vec<PathBuf>, Error> {
+    /// Commit being built from the root repository.
+    pub fn local_head(&self) -> Oid {
+        self.local_head
+    }
+
+    /// Latest commit fetched from the upstream repository.
+    pub fn upstream_head(&self) -> Oid {
+        self.upstream_head
+    }
+
+    pub fn merge_base(&self) -> Result<Oid, Error> {
         let merge_base = self
             .working_repo
             .merge_base(self.local_head, self.upstream_head)
@@ -236,6 +425,12 @@ impl SourceWithUpstream {
             "merge base of `{}` (local) and `{}` (latest) is `{}`",
             self.local_head, self.upstream_head, merge_base
         );
+        Ok(merge_base)
+    }
+
+    /// Compare the merge base to the local head.
+    fn diff_merge_base(&self, options: Option<&mut DiffOptions>) -> Result<Diff<'_>, Error> {
+        let merge_base = self.merge_base()?;
 
         let merge_base_tree = self
             .working_repo
@@ -249,12 +444,15 @@ impl SourceWithUpstream {
             })?;
 
         let master_tree = self.local_head_tree()?;
-        let diff = self
-            .working_repo
-            .diff_tree_to_tree(Some(&merge_base_tree), Some(&master_tree), None)
+        self.working_repo
+            .diff_tree_to_tree(Some(&merge_base_tree), Some(&master_tree), options)
             .context(GitSnafu {
                 what: "comparing merge base to master",
-            })?;
+            })
+    }
+
+    pub fn changed_files(&self) -> Result<Vec<PathBuf>, Error> {
+        let diff = self.diff_merge_base(None)?;
 
         let changed_files = diff
             .deltas()
@@ -265,6 +463,36 @@ impl SourceWithUpstream {
         Ok(changed_files)
     }
 
+    /// Lines added or modified since the merge base, in each changed file.
+    pub fn changed_lines(&self) -> Result<ChangedLines, Error> {
+        let mut options = DiffOptions::new();
+        options.context_lines(0);
+        let diff = self.diff_merge_base(Some(&mut options))?;
+
+        let mut lines: HashMap<PathBuf, Vec<Range<usize>>> = HashMap::new();
+        diff.foreach(
+            &mut |_, _| true,
+            None,
+            Some(&mut |delta, hunk| {
+                // Hunks that only remove lines have nothing to point at.
+                if let Some(path) = delta.new_file().path() {
+                    if hunk.new_lines() > 0 {
+                        let start = hunk.new_start() as usize;
+                        let end = start + hunk.new_lines() as usize;
+                        lines.entry(path.to_owned()).or_default().push(start..end);
+                    }
+                }
+                true
+            }),
+            None,
+        )
+        .context(GitSnafu {
+            what: "list changed lines",
+        })?;
+
+        Ok(ChangedLines { lines })
+    }
+
     fn check_ignored(&self, tree: &Tree) -> Result<(), Error> {
         let mut walk_error = None;
         let walk_result = tree.walk(git2::TreeWalkMode::PreOrder, |a, b| {
@@ -313,17 +541,23 @@ impl SourceWithUpstream {
         Ok(())
     }
 
-    pub fn merge(&self) -> Result<(), Error> {
+    pub fn merge(&self) -> Result<Vec<Merged>, Error> {
         let repo_use = &self.src_repo_use;
         let master_tree = self.local_head_tree()?;
         let mut local_head = self.local_head;
+        let mut merged = Vec::with_capacity(repo_use.other_repos.len());
         for (other_kind, other_repo) in repo_use.other_repos.iter().progress_ext("Merge Repos") {
-            info!("fetching {other_kind} repository");
-            let master_other = fetch(
-                &self.working_repo,
-                other_repo.as_str(),
-                "master:master-other",
-            )?;
+            let master_other = if self.options.offline {
+                info!("using previously fetched {other_kind} repository");
+                find_fetched(&self.working_repo, other_kind)?
+            } else {
+                info!("fetching {other_kind} repository");
+                fetch(
+                    &self.working_repo,
+                    other_repo.as_str(),
+                    &fetched_refspec(other_kind),
+                )?
+            };
             let other_tree = master_other.tree().context(GitSnafu {
                 what: "getting other tree",
             })?;
@@ -421,18 +655,163 @@ impl SourceWithUpstream {
                     what: "checkout merged",
                 })?;
 
-            self.working_repo
-                .find_branch("master-other", BranchType::Local)
+            merged.push(Merged {
+                name: other_kind.clone(),
+                repository: other_repo.clone(),
+                commit: master_other.id(),
+            });
+        }
+
+        Ok(merged)
+    }
+
+    /// Find when each proposal file was last changed, in a single walk over
+    /// the (merged) history of the build repository.
+    ///
+    /// Like `git log -1 -- <path>`, a merge that kept a file unchanged from one
+    /// of its parents is not counted as touching it, and the search continues
+    /// down that parent instead.
+    pub fn last_modified(&self) -> Result<LastModified, Error> {
+        let repo = &self.working_repo;
+        let head = repo
+            .head()
+            .and_then(|h| h.peel_to_commit())
+            .context(GitSnafu {
+                what: "find merged head commit",
+            })?;
+        let head_tree = head.tree().context(GitSnafu {
+            what: "getting merged tree",
+        })?;
+
+        // Paths whose last change hasn't been found yet, keyed by the commit
+        // to look at next.
+        let mut pending: HashMap<Oid, HashSet<PathBuf>> = HashMap::new();
+
+        let prefix = format!("{}/", super::CONTENT_DIR);
+        let walk_result = head_tree.walk(git2::TreeWalkMode::PreOrder, |a, b| {
+            if !a.starts_with(&prefix) && (!a.is_empty() || b.name() != Some(super::CONTENT_DIR)) {
+                return TreeWalkResult::Skip;
+            }
+
+            if let (Some(ObjectType::Blob), Some(name)) = (b.kind(), b.name()) {
+                pending
+                    .entry(head.id())
+                    .or_default()
+                    .insert(PathBuf::from(format!("{a}{name}")));
+            }
+
+            TreeWalkResult::Ok
+        });
+        walk_result.context(GitSnafu {
+            what: "traverse tree",
+        })?;
+
+        let mut revwalk = repo.revwalk().context(GitSnafu {
+            what: "create revwalk",
+        })?;
+        revwalk
+            .set_sorting(Sort::TOPOLOGICAL | Sort::TIME)
+            .context(GitSnafu {
+                what: "sort revwalk",
+            })?;
+        revwalk.push(head.id()).context(GitSnafu {
+            what: "push merged head",
+        })?;
+
+        let mut times = HashMap::new();
+
+        for oid in revwalk {
+            if pending.is_empty() {
+                break;
+            }
+
+            let oid = oid.context(GitSnafu {
+                what: "walk history",
+            })?;
+            let paths = match pending.remove(&oid) {
+                Some(p) => p,
+                None => continue,
+            };
+
+            let commit = repo.find_commit(oid).context(GitSnafu {
+                what: "find commit",
+            })?;
+            let tree = commit.tree().context(GitSnafu {
+                what: "getting commit tree",
+            })?;
+            let seconds = commit.time().seconds();
+
+            let parents = commit
+                .parents()
+                .map(|p| p.tree().map(|t| (p.id(), t)))
+                .collect::<Result<Vec<_>, _>>()
                 .context(GitSnafu {
-                    what: "find master-other",
-                })?
-                .delete()
+                    what: "getting parent tree",
+                })?;
+
+            let (first_id, first_tree) = match parents.first() {
+                Some(f) => f,
+                None => {
+                    times.extend(paths.into_iter().map(|p| (p, seconds)));
+                    continue;
+                }
+            };
+
+            let mut diff_options = DiffOptions::new();
+            diff_options.pathspec(super::CONTENT_DIR);
+            let diff = repo
+                .diff_tree_to_tree(Some(first_tree), Some(&tree), Some(&mut diff_options))
                 .context(GitSnafu {
-                    what: "delete master-other",
+                    what: "comparing commit to parent",
                 })?;
+            let changed: HashSet<&Path> =
+                diff.deltas().filter_map(|d| d.new_file().path()).collect();
+
+            for path in paths {
+                if !changed.contains(path.as_path()) {
+                    pending.entry(*first_id).or_default().insert(path);
+                    continue;
+                }
+
+                let id = tree.get_path(&path).map(|e| e.id()).ok();
+                let same = parents[1..]
+                    .iter()
+                    .find(|(_, t)| t.get_path(&path).map(|e| e.id()).ok() == id);
+
+                match same {
+                    Some((parent_id, _)) => {
+                        pending.entry(*parent_id).or_default().insert(path);
+                    }
+                    None => {
+                        times.insert(path, seconds);
+                    }
+                }
+            }
         }
 
-        Ok(())
+        let times = times
+            .into_iter()
+            .filter_map(|(path, seconds)| {
+                let relative = path.strip_prefix(super::CONTENT_DIR).ok()?;
+                Some((relative.to_path_buf(), seconds))
+            })
+            .collect();
+
+        Ok(LastModified { times })
+    }
+}
+
+/// When each file in the content directory was last changed, as seconds
+/// since the Unix epoch.
+#[derive(Debug, Clone, Default)]
+pub struct LastModified {
+    times: HashMap<PathBuf, i64>,
+}
+
+impl LastModified {
+    /// Look up a path relative to the content directory.
+    pub fn get(&self, path: &Path) -> Option<i64> {
+        self.times.get(path).copied()
     }
 }
 
@@ -467,6 +846,34 @@ fn fetch<'a>(
     Ok(commit)
 }
 
+/// Reference where the `master` branch of the location named `name` is kept
+/// between runs.
+fn fetched_ref(name: &str) -> String {
+    format!("refs/remotes/{name}/master")
+}
+
+fn fetched_refspec(name: &str) -> String {
+    format!("+master:{}", fetched_ref(name))
+}
+
+fn find_fetched<'a>(repo: &'a git2::Repository, name: &str) -> Result<Commit<'a>, Error> {
+    let reference = fetched_ref(name);
+    debug!("looking up `{reference}`");
+    match repo.find_reference(&reference) {
+        Ok(r) => r.peel_to_commit().context(GitSnafu {
+            what: "peel fetched reference",
+        }),
+        Err(e) if e.code() == git2::ErrorCode::NotFound => OfflineSnafu {
+            what: format!("the {name} repository"),
+        }
+        .fail(),
+        Err(e) => Err(GitSnafu {
+            what: "find fetched reference",
+        }
+        .into_error(e)),
+    }
+}
+
 fn open_or_init(dir: &Path) -> Result<git2::Repository, Error> {
     let repo =
         match git2::Repository::open_ext(dir, RepositoryOpenFlags::NO_SEARCH, &[] as &[&OsStr]) {
@@ -487,6 +894,12 @@ impl Cache {
         let repo = open_or_init(&dir)?;
         let object = match repo.revparse_single(commit) {
             Ok(c) => c,
+            Err(e) if e.code() == git2::ErrorCode::NotFound && self.offline() => {
+                return OfflineSnafu {
+                    what: format!("commit `{commit}` of `{url}`"),
+                }
+                .fail();
+            }
             Err(e) if e.code() == git2::ErrorCode::NotFound => {
                 fetch(&repo, url, "master")?;
                 repo.revparse_single(commit).context(GitSnafu {
@@ -8,6 +8,9 @@ use eipw_lint::reporters;
 use eipw_snippets::{annotate_snippets::Renderer, Level, Message, Snippet};
 
 use std::fmt;
+use std::ops::Range;
+
+use crate::span;
 
 fn escape_property(text: &str) -> String {
     text.replace("%", "%25")
@@ -69,12 +72,26 @@ impl fmt::Display for Annotation<'_> {
 }
 
 impl<'a> Annotation<'a> {
-    fn new(title: Option<&'a str>, value: &'a Snippet<'a>) -> Self {
+    /// Annotation covering the bytes in `range` of the snippet's source.
+    fn new(title: Option<&'a str>, value: &'a Snippet<'a>, range: Range<usize>) -> Self {
+        // GitHub's end column is inclusive, so point at the last character.
+        let start = span::position(value, range.start);
+        let end = span::position(value, range.end.saturating_sub(1).max(range.start));
+
+        // Columns are ignored when the annotation spans more than one line.
+        let (start_column, end_column) = if start.line == end.line {
+            (Some(start.column), Some(end.column))
+        } else {
+            (None, None)
+        };
+
         Self {
             title,
-            start_line: Some(value.line_start),
             file: value.origin.as_deref(),
-            ..Default::default()
+            start_line: Some(start.line),
+            end_line: Some(end.line),
+            start_column,
+            end_column,
         }
     }
 }
@@ -129,11 +146,53 @@ impl reporters::Reporter for Reporter {
                 snippet.origin = Some(stripped.to_string().into());
             }
 
+            // One annotation for each labelled span, or for the first span
+            // (or the whole snippet) if none are labelled.
             for snippet in &msg.snippets {
-                Self::print(&msg, Annotation::new(Some(&msg.title), snippet));
+                let mut ranges: Vec<_> = snippet
+                    .annotations
+                    .iter()
+                    .filter(|a| a.label.is_some())
+                    .map(|a| a.range.clone())
+                    .collect();
+
+                if ranges.is_empty() {
+                    ranges.push(match snippet.annotations.first() {
+                        Some(a) => a.range.clone(),
+                        None => span::whole(snippet),
+                    });
+                }
+
+                for range in ranges {
+                    Self::print(&msg, Annotation::new(Some(&msg.title), snippet, range));
+                }
             }
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn annotation_ranges() {
+        let snippet = Snippet::source("eip: 1\ntitle: Zoë\n")
+            .line_start(2)
+            .origin("content/00001.md");
+
+        let single = Annotation::new(None, &snippet, 14..18);
+        assert_eq!(
+            single.to_string(),
+            "file=content/00001.md,line=3,endLine=3,col=8,endCol=10"
+        );
+
+        let multiple = Annotation::new(None, &snippet, 5..12);
+        assert_eq!(
+            multiple.to_string(),
+            "file=content/00001.md,line=2,endLine=3"
+        );
+    }
+}
@@ -0,0 +1,266 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Reuse preprocessed markdown from earlier builds.
+//!
+//! The output of preprocessing a file depends on its contents, its path, and
+//! when it was last modified (plus the tool version, output [`FORMAT`], and
+//! theme commit), which are hashed together into a key. It also depends on how
+//! its links resolve against the rest of the tree, so those resolutions are
+//! recorded alongside the output and checked again before it is reused.
+
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use git2::{ObjectType, Oid};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use snafu::{Backtrace, Report, ResultExt, Snafu};
+use toml_datetime::Datetime;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const OBJECTS_DIR: &str = "objects";
+
+/// Version of the preprocessed output. Bump this whenever preprocessing
+/// changes what it writes, so outputs from older builds of the same release
+/// aren't reused.
+const FORMAT: &str = "1";
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("i/o error accessing `{}`", path.to_string_lossy()))]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("unable to serialize `{}`", path.to_string_lossy()))]
+    Serialize {
+        path: PathBuf,
+        source: serde_json::Error,
+        backtrace: Backtrace,
+    },
+}
+
+/// A link (or `requires` entry) resolved to an `@/` path while preprocessing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Link {
+    /// Directory the link is relative to, relative to the content directory.
+    pub parent: PathBuf,
+    pub input: String,
+    pub output: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    links: Vec<Link>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    entries: HashMap<String, Entry>,
+}
+
+/// Identifies one preprocessed file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Key(String);
+
+/// Preprocessed outputs kept between builds, in `build/preprocess`.
+#[derive(Debug)]
+pub struct Store {
+    dir: PathBuf,
+    theme_commit: String,
+    previous: Manifest,
+    current: Mutex<Manifest>,
+}
+
+impl Store {
+    pub fn open(dir: &Path, theme_commit: &str) -> Result<Self, Error> {
+        let objects = dir.join(OBJECTS_DIR);
+        std::fs::create_dir_all(&objects).context(IoSnafu { path: &objects })?;
+
+        let manifest_path = dir.join(MANIFEST_FILE);
+        let previous = match std::fs::read(&manifest_path) {
+            Ok(bytes) => match serde_json::from_slice(&bytes) {
+                Ok(m) => m,
+                Err(e) => {
+                    warn!(
+                        "ignoring unreadable manifest `{}`: {}",
+                        manifest_path.to_string_lossy(),
+                        Report::from_error(e)
+                    );
+                    Manifest::default()
+                }
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => Manifest::default(),
+            Err(e) => {
+                return Err(e).context(IoSnafu {
+                    path: manifest_path,
+                })
+            }
+        };
+
+        debug!(
+            "{} preprocessed files from previous builds",
+            previous.entries.len()
+        );
+
+        Ok(Self {
+            dir: dir.to_owned(),
+            theme_commit: theme_commit.to_owned(),
+            previous,
+            current: Default::default(),
+        })
+    }
+
+    /// Compute the key for the file at `path` (relative to the content
+    /// directory) with the given unprocessed `contents`.
+    pub fn key(&self, path: &Path, contents: &[u8], updated: Option<&Datetime>) -> Key {
+        let blob = Oid::hash_object(ObjectType::Blob, contents).expect("hashing blob");
+
+        let mut hasher = Sha3_256::new();
+        for part in [
+            env!("CARGO_PKG_VERSION"),
+            FORMAT,
+            &self.theme_commit,
+            &path.to_string_lossy(),
+            &blob.to_string(),
+            &updated.map(Datetime::to_string).unwrap_or_default(),
+        ] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+
+        Key(format!("{:x}", hasher.finalize()))
+    }
+
+    fn object_path(&self, key: &Key) -> PathBuf {
+        self.dir.join(OBJECTS_DIR).join(&key.0)
+    }
+
+    /// Previously preprocessed output for `key`, if any, and if `valid`
+    /// accepts the links it was produced with.
+    pub fn get(&self, key: &Key, valid: impl FnOnce(&[Link]) -> bool) -> Option<String> {
+        let entry = self.previous.entries.get(&key.0)?;
+        if !valid(&entry.links) {
+            debug!("links changed for `{}`", key.0);
+            return None;
+        }
+
+        let path = self.object_path(key);
+        let output = match std::fs::read_to_string(&path) {
+            Ok(o) => o,
+            Err(e) => {
+                debug!(
+                    "unable to read `{}`: {}",
+                    path.to_string_lossy(),
+                    Report::from_error(e)
+                );
+                return None;
+            }
+        };
+
+        self.current
+            .lock()
+            .unwrap()
+            .entries
+            .insert(key.0.clone(), entry.clone());
+
+        Some(output)
+    }
+
+    /// Remember the `output` produced for `key`, and the `links` it depends on.
+    pub fn insert(&self, key: Key, links: Vec<Link>, output: &str) -> Result<(), Error> {
+        let path = self.object_path(&key);
+        std::fs::write(&path, output).context(IoSnafu { path })?;
+
+        self.current
+            .lock()
+            .unwrap()
+            .entries
+            .insert(key.0, Entry { links });
+
+        Ok(())
+    }
+
+    /// Write out the manifest for this build, and forget any outputs it
+    /// didn't use.
+    pub fn save(self) -> Result<(), Error> {
+        let current = self.current.into_inner().unwrap();
+
+        let manifest_path = self.dir.join(MANIFEST_FILE);
+        let json = serde_json::to_vec(&current).context(SerializeSnafu {
+            path: &manifest_path,
+        })?;
+        std::fs::write(&manifest_path, json).context(IoSnafu {
+            path: &manifest_path,
+        })?;
+
+        let objects = self.dir.join(OBJECTS_DIR);
+        let dir = std::fs::read_dir(&objects).context(IoSnafu { path: &objects })?;
+        for entry in dir {
+            let entry = entry.context(IoSnafu { path: &objects })?;
+            let used = entry
+                .file_name()
+                .to_str()
+                .map(|n| current.entries.contains_key(n))
+                .unwrap_or(false);
+            if !used {
+                let path = entry.path();
+                std::fs::remove_file(&path).context(IoSnafu { path })?;
+            }
+        }
+
+        debug!("kept {} preprocessed files", current.entries.len());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuses_output_across_builds() {
+        let dir = tempfile::tempdir().unwrap();
+        let link = Link {
+            parent: PathBuf::new(),
+            input: "./00001.md".into(),
+            output: "@/00001.md".into(),
+        };
+
+        let store = Store::open(dir.path(), "abc").unwrap();
+        let key = store.key(Path::new("00002.md"), b"contents", None);
+        assert_eq!(store.get(&key, |_| true), None);
+        store
+            .insert(key.clone(), vec![link.clone()], "output")
+            .unwrap();
+        store.save().unwrap();
+
+        let store = Store::open(dir.path(), "abc").unwrap();
+        assert_eq!(
+            store.get(&key, |links| links == [link.clone()]).as_deref(),
+            Some("output")
+        );
+        assert_eq!(store.get(&key, |_| false), None);
+        store.save().unwrap();
+
+        let store = Store::open(dir.path(), "def").unwrap();
+        let other = store.key(Path::new("00002.md"), b"contents", None);
+        assert_ne!(key, other);
+        assert_eq!(store.get(&other, |_| true), None);
+        store.save().unwrap();
+
+        let store = Store::open(dir.path(), "abc").unwrap();
+        assert_eq!(store.get(&key, |_| true), None);
+    }
+}
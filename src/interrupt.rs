@@ -0,0 +1,90 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Ctrl-C handling.
+//!
+//! An interrupt kills the registered child process (if there is one) and sets
+//! a flag, which long running steps poll with [`check`] so the caller can
+//! unwind normally (and release its locks.) A second interrupt exits
+//! immediately, in case something stops polling.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+use duct::ReaderHandle;
+use log::{debug, info, warn};
+use snafu::{ensure, Backtrace, Report, Snafu};
+
+use crate::progress;
+
+/// Exit code conventionally used after receiving `SIGINT`.
+const EXIT_INTERRUPTED: i32 = 130;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static CHILD: Mutex<Option<Arc<ReaderHandle>>> = Mutex::new(None);
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("interrupted"))]
+    Interrupted { backtrace: Backtrace },
+    #[snafu(display("child process already registered"))]
+    Registered { backtrace: Backtrace },
+}
+
+fn handle() {
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        progress::clear();
+        std::process::exit(EXIT_INTERRUPTED);
+    }
+
+    let child = CHILD.lock().unwrap();
+    let child = match &*child {
+        Some(c) => c,
+        None => {
+            warn!("interrupted, stopping (interrupt again to exit immediately)");
+            return;
+        }
+    };
+
+    info!("interrupted, stopping child process");
+    if let Err(e) = child.kill() {
+        debug!("got while killing child: {}", Report::from_error(e));
+    }
+}
+
+pub fn init() -> Result<(), ctrlc::Error> {
+    ctrlc::set_handler(handle)
+}
+
+/// Whether an interrupt has been received.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Fails once an interrupt has been received.
+pub fn check() -> Result<(), Error> {
+    ensure!(!interrupted(), InterruptedSnafu);
+    Ok(())
+}
+
+/// Kills the registered child on interrupt, until dropped.
+#[must_use]
+pub struct Guard(());
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        CHILD.lock().unwrap().take();
+    }
+}
+
+pub fn register(child: Arc<ReaderHandle>) -> Result<Guard, Error> {
+    let mut registered = CHILD.lock().unwrap();
+    ensure!(registered.is_none(), RegisteredSnafu);
+    *registered = Some(child);
+    Ok(Guard(()))
+}
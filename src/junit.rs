@@ -0,0 +1,239 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! JUnit XML output, so CI dashboards can show lint problems as failing
+//! tests.
+//!
+//! Each proposal is a test suite, and each lint that ran on it is a test case,
+//! which fails if the lint reported any errors.
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt::{self, Write},
+    path::Path,
+};
+
+use eipw_lint::reporters;
+use eipw_snippets::{annotate_snippets::Renderer, Level, Message};
+
+/// Suite and case name for problems that don't come from a lint, or a file.
+const FALLBACK_NAME: &str = "build-eips";
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Not allowed in XML 1.0 at all.
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => (),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[derive(Debug)]
+struct Problem {
+    level: Level,
+    title: String,
+    rendered: String,
+}
+
+/// Problems reported for each file, then for each lint.
+type Suites = BTreeMap<String, BTreeMap<String, Vec<Problem>>>;
+
+#[derive(Debug)]
+pub struct Reporter {
+    root: String,
+    suites: RefCell<Suites>,
+}
+
+impl Reporter {
+    pub fn new(root: String) -> Self {
+        Self {
+            root,
+            suites: Default::default(),
+        }
+    }
+
+    fn relative<'a>(&self, origin: &'a str) -> &'a str {
+        let stripped = origin.strip_prefix(&self.root).unwrap_or(origin);
+        stripped.strip_prefix('/').unwrap_or(stripped)
+    }
+
+    /// Record that the given lints ran on `path`, so they show up as passing
+    /// tests if they didn't report anything.
+    pub fn ran<I>(&self, path: &Path, lints: I)
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let path = path.to_string_lossy();
+        let mut suites = self.suites.borrow_mut();
+        let cases = suites.entry(self.relative(&path).to_owned()).or_default();
+        for lint in lints {
+            cases.entry(lint.into()).or_default();
+        }
+    }
+
+    fn write_case(
+        f: &mut impl Write,
+        suite: &str,
+        case: &str,
+        problems: &[Problem],
+    ) -> fmt::Result {
+        write!(
+            f,
+            "    <testcase name=\"{}\" classname=\"{}\"",
+            escape(case),
+            escape(suite)
+        )?;
+
+        if problems.is_empty() {
+            return writeln!(f, "/>");
+        }
+        writeln!(f, ">")?;
+
+        let (errors, others): (Vec<_>, Vec<_>) = problems
+            .iter()
+            .partition(|p| matches!(p.level, Level::Error));
+
+        if let Some(first) = errors.first() {
+            write!(
+                f,
+                "      <failure message=\"{}\" type=\"error\">",
+                escape(&first.title)
+            )?;
+            for error in &errors {
+                writeln!(f, "{}", escape(&error.rendered))?;
+            }
+            writeln!(f, "</failure>")?;
+        }
+
+        if !others.is_empty() {
+            write!(f, "      <system-out>")?;
+            for other in &others {
+                writeln!(f, "{}", escape(&other.rendered))?;
+            }
+            writeln!(f, "</system-out>")?;
+        }
+
+        writeln!(f, "    </testcase>")
+    }
+
+    /// The complete JUnit XML document for everything reported so far.
+    pub fn xml(&self) -> String {
+        let suites = self.suites.borrow();
+
+        let failed =
+            |problems: &Vec<Problem>| problems.iter().any(|p| matches!(p.level, Level::Error));
+
+        let tests: usize = suites.values().map(BTreeMap::len).sum();
+        let failures: usize = suites
+            .values()
+            .map(|cases| cases.values().filter(|p| failed(p)).count())
+            .sum();
+
+        let mut xml = String::new();
+        let f = &mut xml;
+
+        writeln!(f, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>").unwrap();
+        writeln!(
+            f,
+            "<testsuites name=\"{FALLBACK_NAME}\" tests=\"{tests}\" failures=\"{failures}\">"
+        )
+        .unwrap();
+
+        for (suite, cases) in suites.iter() {
+            let failures = cases.values().filter(|p| failed(p)).count();
+            writeln!(
+                f,
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{failures}\">",
+                escape(suite),
+                cases.len(),
+            )
+            .unwrap();
+
+            for (case, problems) in cases {
+                Self::write_case(f, suite, case, problems).unwrap();
+            }
+
+            writeln!(f, "  </testsuite>").unwrap();
+        }
+
+        writeln!(f, "</testsuites>").unwrap();
+
+        xml
+    }
+}
+
+impl reporters::Reporter for Reporter {
+    fn report(&self, mut msg: Message<'_>) -> Result<(), reporters::Error> {
+        for snippet in &mut msg.snippets {
+            if let Some(origin) = &snippet.origin {
+                snippet.origin = Some(self.relative(origin).to_owned().into());
+            }
+        }
+
+        let suite = msg
+            .snippets
+            .iter()
+            .find_map(|s| s.origin.as_deref())
+            .unwrap_or(FALLBACK_NAME)
+            .to_owned();
+        let case = msg.id.as_deref().unwrap_or(FALLBACK_NAME).to_owned();
+
+        let rendered = Renderer::plain().render((&msg).into()).to_string();
+
+        self.suites
+            .borrow_mut()
+            .entry(suite)
+            .or_default()
+            .entry(case)
+            .or_default()
+            .push(Problem {
+                level: msg.level,
+                title: msg.title.to_string(),
+                rendered,
+            });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eipw_lint::reporters::Reporter as _;
+    use eipw_snippets::Snippet;
+
+    #[test]
+    fn failing_and_passing_cases() {
+        let reporter = Reporter::new("/repo".into());
+        reporter.ran(
+            Path::new("/repo/content/00001.md"),
+            ["preamble-author", "preamble-title"],
+        );
+
+        let message = Level::Error
+            .title("preamble has <no> author")
+            .id("preamble-author")
+            .snippet(Snippet::source("eip: 1").origin("/repo/content/00001.md"));
+        reporter.report(message).unwrap();
+
+        let xml = reporter.xml();
+        assert!(xml.contains("<testsuites name=\"build-eips\" tests=\"2\" failures=\"1\">"));
+        assert!(xml.contains("<testsuite name=\"content/00001.md\" tests=\"2\" failures=\"1\">"));
+        assert!(xml.contains("<testcase name=\"preamble-title\" classname=\"content/00001.md\"/>"));
+        assert!(xml.contains("<failure message=\"preamble has &lt;no&gt; author\" type=\"error\">"));
+        assert!(xml.contains("--&gt; content/00001.md"));
+    }
+}
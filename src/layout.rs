@@ -8,3 +8,8 @@ pub(crate) const CONTENT_DIR: &str = "content";
 pub(crate) const BUILD_DIR: &str = "build";
 pub(crate) const REPO_DIR: &str = "repo";
 pub(crate) const OUTPUT_DIR: &str = "output";
+pub(crate) const PREPROCESS_DIR: &str = "preprocess";
+pub(crate) const REPORT_FILE: &str = "report.json";
+
+/// Lint configuration for one proposal, next to its `index.md`.
+pub(crate) const OVERRIDES_FILE: &str = "eipw.toml";
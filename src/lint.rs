@@ -4,15 +4,29 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use eipw_lint::config::DefaultOptions;
-use eipw_snippets::Message;
+use eipw_lint::config::{DefaultOptions, Options, Override};
+use eipw_lint::lints::DefaultLint;
+use eipw_lint::modifiers::DefaultModifier;
+use eipw_snippets::{Level, Message, Snippet};
 
 use clap::ValueEnum;
-use log::debug;
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use regex::Regex;
 use semver::{Comparator, Op, VersionReq};
 
+use crate::baseline::{self, Baseline};
 use crate::cache::Cache;
+use crate::changed;
+use crate::config::{LintOverrides, Theme};
+use crate::git::ChangedLines;
+use crate::interrupt;
+use crate::layout::{CONTENT_DIR, OVERRIDES_FILE};
 use crate::progress::ProgressIteratorExt;
+use crate::report;
+use crate::span;
+use crate::timings::{self, Spent, Timed, Timings};
+use crate::zola::{Diagnostic, Severity};
 
 use eipw_lint::reporters::{AdditionalHelp, Count, Json, Reporter, Text};
 use eipw_lint::Linter;
@@ -21,11 +35,20 @@ use figment::providers::{Format as _, Serialized, Toml};
 use figment::Figment;
 use serde::{Deserialize, Serialize};
 
-use snafu::{ensure, Backtrace, IntoError, OptionExt, ResultExt, Snafu};
+use snafu::{ensure, whatever, Backtrace, IntoError, OptionExt, ResultExt, Snafu, Whatever};
 
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsStr;
 use std::io::{ErrorKind, Write};
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::runtime::Handle;
+use tokio::sync::Semaphore;
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -35,6 +58,13 @@ pub enum Error {
         source: Box<figment::Error>,
         backtrace: Backtrace,
     },
+    #[snafu(display("unable to apply lint overrides for `{}`", path.to_string_lossy()))]
+    Override {
+        path: PathBuf,
+        #[snafu(source(from(figment::Error, Box::new)))]
+        source: Box<figment::Error>,
+        backtrace: Backtrace,
+    },
     #[snafu(display("no lint `{name}` is enabled"))]
     NoLint { name: String, backtrace: Backtrace },
     #[snafu(transparent)]
@@ -55,6 +85,32 @@ pub enum Error {
         #[snafu(backtrace)]
         source: crate::git::Error,
     },
+    #[snafu(display("unable to lint `{}`", path.to_string_lossy()))]
+    Thread {
+        path: PathBuf,
+        source: Detached,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("unable to start async runtime"))]
+    Runtime {
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("unable to report problems"))]
+    Report {
+        source: eipw_lint::reporters::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(transparent)]
+    Baseline {
+        #[snafu(backtrace)]
+        source: crate::baseline::Error,
+    },
+    #[snafu(transparent)]
+    Interrupt {
+        #[snafu(backtrace)]
+        source: interrupt::Error,
+    },
     #[snafu(transparent)]
     SchemaVersion {
         #[snafu(backtrace)]
@@ -62,6 +118,35 @@ pub enum Error {
     },
 }
 
+/// Copy of an error (and its sources) from another thread, since eipw's errors
+/// can't be sent between threads.
+#[derive(Debug)]
+pub struct Detached {
+    message: String,
+    source: Option<Box<Detached>>,
+}
+
+impl Detached {
+    fn new(error: &dyn std::error::Error) -> Self {
+        Self {
+            message: error.to_string(),
+            source: error.source().map(|s| Box::new(Self::new(s))),
+        }
+    }
+}
+
+impl std::fmt::Display for Detached {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Detached {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.as_deref().map(|s| s as _)
+    }
+}
+
 #[derive(Debug, Snafu)]
 #[snafu(display(
     "eipw configuration (`{}`) is incompatible with this application (`{}`)",
@@ -92,7 +177,7 @@ struct Config {
     eipw: eipw_lint::config::DefaultOptions,
 }
 
-#[derive(Debug, clap::Args, Serialize, Deserialize)]
+#[derive(Debug, Clone, clap::Args, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct CmdArgs {
     /// Disable linting entirely
@@ -103,10 +188,116 @@ pub struct CmdArgs {
     #[clap(required(false))]
     sources: Vec<PathBuf>,
 
+    /// Lint every proposal (EIPs and ERCs), not just changed ones, and
+    /// summarize the problems found by each lint
+    #[clap(long, conflicts_with("sources"))]
+    all: bool,
+
     /// Lint output format
     #[clap(long, value_enum, default_value_t)]
     format: Format,
 
+    /// Don't report problems recorded in this baseline file
+    #[clap(long)]
+    baseline: Option<PathBuf>,
+
+    /// Record the problems found in the `--baseline` file, instead of
+    /// reporting them
+    #[clap(long, requires("baseline"))]
+    write_baseline: bool,
+
+    /// Only report problems on lines changed since the merge base with
+    /// upstream (leave off for the full report)
+    #[clap(long)]
+    changed_lines: bool,
+
+    /// Do not enable the default lints
+    #[clap(long)]
+    no_default_lints: bool,
+
+    /// Lints to enable as errors
+    #[clap(long, short('D'))]
+    deny: Vec<String>,
+
+    /// Lints to enable as warnings
+    #[clap(long, short('W'))]
+    warn: Vec<String>,
+
+    /// Lints to disable
+    #[clap(long, short('A'))]
+    allow: Vec<String>,
+
+    /// Number of proposals to lint at once
+    #[clap(long, short('j'), default_value = "1")]
+    jobs: NonZeroUsize,
+
+    /// Print how long each proposal and lint took (to stderr)
+    #[clap(
+        long,
+        value_enum,
+        num_args(0..=1),
+        require_equals(true),
+        default_missing_value("table")
+    )]
+    timings: Option<timings::Format>,
+}
+
+impl CmdArgs {
+    /// Whether specific sources (or `--all`) were given on the command line.
+    pub fn has_sources(&self) -> bool {
+        self.all || !self.sources.is_empty()
+    }
+
+    /// Fail unless specific sources (or `--all`) were given, since there'd be
+    /// nothing to `verb`.
+    pub fn require_sources(&self, verb: &str) -> Result<(), Whatever> {
+        if !self.has_sources() {
+            whatever!("nothing to {verb} (give some files or directories, or use `--all`)");
+        }
+        Ok(())
+    }
+
+    /// Whether `--changed-lines` was given.
+    pub fn changed_lines(&self) -> bool {
+        self.changed_lines
+    }
+
+    /// Copy of these arguments that lints `sources` instead of any given on
+    /// the command line.
+    pub fn with_sources(&self, sources: Vec<PathBuf>) -> Self {
+        Self {
+            sources,
+            all: false,
+            ..self.clone()
+        }
+    }
+
+    /// Copy of these arguments for relinting files as they change: only the
+    /// paths passed to [`eipw`] are linted (instead of any `sources`, or
+    /// `--all`), and neither the baseline nor timings are written.
+    pub fn for_reload(&self) -> Self {
+        Self {
+            sources: Vec::new(),
+            all: false,
+            write_baseline: false,
+            timings: None,
+            ..self.clone()
+        }
+    }
+}
+
+/// Which proposals to change, and which lints to fix them for, for commands
+/// that rewrite proposals instead of reporting on them.
+#[derive(Debug, Clone, clap::Args)]
+pub struct SourceArgs {
+    /// Only change specific files and/or directories (relative to project root)
+    #[clap(required(false))]
+    sources: Vec<PathBuf>,
+
+    /// Change every proposal (EIPs and ERCs)
+    #[clap(long, conflicts_with("sources"))]
+    all: bool,
+
     /// Do not enable the default lints
     #[clap(long)]
     no_default_lints: bool,
@@ -124,6 +315,26 @@ pub struct CmdArgs {
     allow: Vec<String>,
 }
 
+impl From<SourceArgs> for CmdArgs {
+    fn from(args: SourceArgs) -> Self {
+        Self {
+            no_lint: false,
+            sources: args.sources,
+            all: args.all,
+            format: Format::default(),
+            baseline: None,
+            write_baseline: false,
+            changed_lines: false,
+            no_default_lints: args.no_default_lints,
+            deny: args.deny,
+            warn: args.warn,
+            allow: args.allow,
+            jobs: NonZeroUsize::MIN,
+            timings: None,
+        }
+    }
+}
+
 #[derive(Default, ValueEnum, Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 enum Format {
@@ -133,6 +344,9 @@ enum Format {
     #[serde(rename = "github")]
     #[clap(name = "github")]
     GitHub,
+    Sarif,
+    Junit,
+    Rdjson,
 }
 
 #[derive(Debug)]
@@ -140,20 +354,157 @@ enum EitherReporter {
     Text(Text<String>),
     Json(Json),
     GitHub(crate::github::Reporter),
+    Sarif(crate::sarif::Reporter),
+    Junit(crate::junit::Reporter),
+    Rdjson(crate::rdjson::Reporter),
 }
 
-impl Reporter for EitherReporter {
+impl Reporter for &EitherReporter {
     fn report(&self, snippet: Message<'_>) -> Result<(), eipw_lint::reporters::Error> {
         match self {
-            Self::Text(s) => s.report(snippet),
-            Self::Json(j) => j.report(snippet),
-            Self::GitHub(g) => g.report(snippet),
+            EitherReporter::Text(s) => s.report(snippet),
+            EitherReporter::Json(j) => j.report(snippet),
+            EitherReporter::GitHub(g) => g.report(snippet),
+            EitherReporter::Sarif(s) => s.report(snippet),
+            EitherReporter::Junit(j) => j.report(snippet),
+            EitherReporter::Rdjson(r) => r.report(snippet),
+        }
+    }
+}
+
+/// Documentation for the lint with the given slug.
+pub fn help_url(slug: &str) -> String {
+    format!("https://ethereum.github.io/eipw/{slug}/")
+}
+
+lazy_static! {
+    // Matches internal link targets in zola's messages.
+    static ref RE_ZOLA_TARGET: Regex = Regex::new(r"`@/([^`]+)`").unwrap();
+}
+
+/// Collects lint results (and other problems found while building) and
+/// writes them out in the format chosen with `--format`.
+#[derive(Debug)]
+pub struct Output {
+    repo_dir: PathBuf,
+    reporter: EitherReporter,
+    linted: RefCell<report::Lint>,
+}
+
+impl Output {
+    pub fn new(opts: &CmdArgs, repo_dir: &Path) -> Result<Self, Error> {
+        let repo_dir = std::fs::canonicalize(repo_dir).context(FsSnafu { path: repo_dir })?;
+
+        let reporter = match opts.format {
+            Format::Json => EitherReporter::Json(Json::default()),
+            Format::Text => EitherReporter::Text(Text::default()),
+            Format::GitHub => EitherReporter::GitHub(crate::github::Reporter {
+                root: repo_dir.to_str().expect("repository dir not UTF-8").into(),
+            }),
+            Format::Sarif => EitherReporter::Sarif(crate::sarif::Reporter::new(
+                repo_dir.to_str().expect("repository dir not UTF-8").into(),
+            )),
+            Format::Junit => EitherReporter::Junit(crate::junit::Reporter::new(
+                repo_dir.to_str().expect("repository dir not UTF-8").into(),
+            )),
+            Format::Rdjson => EitherReporter::Rdjson(crate::rdjson::Reporter::new(
+                repo_dir.to_str().expect("repository dir not UTF-8").into(),
+            )),
+        };
+
+        Ok(Self {
+            repo_dir,
+            reporter,
+            linted: Default::default(),
+        })
+    }
+
+    /// Which files were linted, and what was found.
+    pub fn linted(&self) -> report::Lint {
+        self.linted.borrow().clone()
+    }
+
+    /// Report problems found by zola.
+    ///
+    /// Zola only sees preprocessed markdown, so snippets are taken from the
+    /// matching file in the root checkout (when there is one.)
+    pub fn zola(&self, root_dir: &Path, diagnostics: &[Diagnostic]) -> Result<(), Error> {
+        for diagnostic in diagnostics {
+            let level = match diagnostic.severity {
+                Severity::Error => Level::Error,
+                Severity::Warning => Level::Warning,
+            };
+
+            let file = diagnostic
+                .file
+                .as_deref()
+                .and_then(|f| std::fs::canonicalize(f).ok());
+            let relative = file
+                .as_deref()
+                .and_then(|f| f.strip_prefix(&self.repo_dir).ok());
+
+            let source = match relative {
+                Some(r) => std::fs::read_to_string(root_dir.join(r)).ok(),
+                None => None,
+            };
+
+            let origin = relative.map(|r| self.repo_dir.join(r).to_string_lossy().into_owned());
+
+            let mut message = level.title(&diagnostic.message);
+
+            if let (Some(origin), Some(source)) = (&origin, &source) {
+                let needle = RE_ZOLA_TARGET
+                    .captures(&diagnostic.message)
+                    .map(|c| c.get(1).unwrap().as_str());
+
+                let found = needle.and_then(|needle| {
+                    source
+                        .lines()
+                        .enumerate()
+                        .find_map(|(n, l)| l.find(needle).map(|c| (n, l, c..c + needle.len())))
+                });
+
+                // Without the target, there's no telling which line to point
+                // at, so the message stands alone.
+                if let Some((n, line, range)) = found {
+                    let snippet = Snippet::source(line)
+                        .line_start(n + 1)
+                        .origin(origin)
+                        .annotation(level.span(range));
+                    message = message.snippet(snippet);
+                }
+            }
+
+            (&self.reporter).report(message).context(ReportSnafu)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(self) {
+        let mut stdout = std::io::stdout();
+        match self.reporter {
+            EitherReporter::Json(j) => serde_json::to_writer_pretty(&stdout, &j).unwrap(),
+            EitherReporter::Text(t) => write!(stdout, "{}", t.into_inner()).unwrap(),
+            EitherReporter::GitHub(_) => (),
+            EitherReporter::Sarif(s) => serde_json::to_writer_pretty(&stdout, &s.log()).unwrap(),
+            EitherReporter::Junit(j) => write!(stdout, "{}", j.xml()).unwrap(),
+            EitherReporter::Rdjson(r) => {
+                serde_json::to_writer_pretty(&stdout, &r.result()).unwrap()
+            }
+        }
+    }
+
+    /// Record which lints ran on `path`, for formats that list passing lints
+    /// too.
+    fn ran(&self, path: &Path, lints: &HashMap<String, Duration>) {
+        if let EitherReporter::Junit(j) = &self.reporter {
+            j.ran(path, lints.keys());
         }
     }
 }
 
 async fn collect_sources(sources: Vec<PathBuf>) -> Result<Vec<PathBuf>, Error> {
-    use std::ffi::OsStr;
     use tokio::fs;
 
     let mut output = Vec::with_capacity(sources.len());
@@ -255,28 +606,334 @@ fn version_cmp(
     Ok(())
 }
 
-#[tokio::main(flavor = "current_thread")]
-pub async fn eipw(
-    theme_repo: &str,
-    theme_rev: &str,
-    cache: &Cache,
+/// Copy of `message` that doesn't borrow from the linter.
+fn owned(message: Message<'_>) -> Message<'static> {
+    let mut copy = message.level.title("");
+    copy.id = message.id.map(|i| Cow::Owned(i.into_owned()));
+    copy.title = Cow::Owned(message.title.into_owned());
+    copy.snippets = message
+        .snippets
+        .into_iter()
+        .map(|s| {
+            let mut snippet = Snippet::source("").line_start(s.line_start).fold(s.fold);
+            snippet.origin = s.origin.map(|o| Cow::Owned(o.into_owned()));
+            snippet.source = Cow::Owned(s.source.into_owned());
+            snippet.annotations = s
+                .annotations
+                .into_iter()
+                .map(|a| {
+                    let mut annotation = a.level.span(a.range);
+                    annotation.label = a.label.map(|l| Cow::Owned(l.into_owned()));
+                    annotation
+                })
+                .collect();
+            snippet
+        })
+        .collect();
+    copy.footer = message.footer.into_iter().map(owned).collect();
+    copy
+}
+
+/// Reporter that keeps messages around, so they can be sent to another
+/// thread and reported from there.
+#[derive(Debug, Default)]
+struct Collect(RefCell<Vec<Message<'static>>>);
+
+impl Reporter for Collect {
+    fn report(&self, message: Message<'_>) -> Result<(), eipw_lint::reporters::Error> {
+        self.0.borrow_mut().push(owned(message));
+        Ok(())
+    }
+}
+
+/// Reporter that tallies problems by lint, for `--all`.
+#[derive(Debug)]
+struct Summary<R> {
+    inner: R,
+    counts: RefCell<BTreeMap<String, (usize, usize)>>,
+}
+
+impl<R> Summary<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            counts: Default::default(),
+        }
+    }
+
+    /// Write the tallies to stderr, so they don't mix with lint output.
+    fn print(&self) {
+        let counts = self.counts.borrow();
+        let mut stderr = std::io::stderr().lock();
+        writeln!(stderr, "{:>8}  {:>8}  Lint", "Errors", "Warnings").unwrap();
+        for (slug, (errors, warnings)) in counts.iter() {
+            writeln!(stderr, "{errors:>8}  {warnings:>8}  {slug}").unwrap();
+        }
+    }
+}
+
+impl<R: Reporter> Reporter for Summary<R> {
+    fn report(&self, message: Message<'_>) -> Result<(), eipw_lint::reporters::Error> {
+        let slug = message.id.as_deref().unwrap_or("(other)");
+        let mut counts = self.counts.borrow_mut();
+        let entry = counts.entry(slug.to_owned()).or_default();
+        match message.level {
+            Level::Error => entry.0 += 1,
+            Level::Warning => entry.1 += 1,
+            _ => (),
+        }
+        drop(counts);
+
+        self.inner.report(message)
+    }
+}
+
+/// Whether `message` points at any changed lines. Messages that can't be tied
+/// to a file are always reported.
+fn touches(message: &Message<'_>, repo_dir: &Path, changed: &ChangedLines) -> bool {
+    if message.snippets.is_empty() {
+        return true;
+    }
+
+    message.snippets.iter().any(|snippet| {
+        let origin = match snippet.origin.as_deref() {
+            Some(o) => Path::new(o),
+            None => return true,
+        };
+        let path = origin.strip_prefix(repo_dir).unwrap_or(origin);
+
+        let mut ranges: Vec<_> = snippet
+            .annotations
+            .iter()
+            .map(|a| a.range.clone())
+            .collect();
+        if ranges.is_empty() {
+            ranges.push(span::whole(snippet));
+        }
+
+        ranges.into_iter().any(|range| {
+            let span = span::span(snippet, &range);
+            changed.touches(path, span.start.line..span.end.line + 1)
+        })
+    })
+}
+
+/// Results of linting one file.
+#[derive(Debug)]
+struct Linted {
+    messages: Vec<Message<'static>>,
+    elapsed: Duration,
+    spent: HashMap<String, Duration>,
+}
+
+fn linter<'a, R>(
+    reporter: R,
+    options: DefaultOptions<String>,
+    opts: &CmdArgs,
+    spent: &Spent,
+) -> Result<Linter<'a, R>, Error> {
+    let mut timed = Options::<DefaultModifier<String>, Timed<DefaultLint<String>>>::default();
+    timed.modifiers = options.modifiers;
+    timed.fetch = options.fetch;
+    timed.lints = options
+        .lints
+        .into_iter()
+        .map(|(slug, lint)| {
+            let lint = match lint.into_lint() {
+                Some(l) => Override::enable(Timed::new(&slug, l, spent)),
+                None => Override::disable(),
+            };
+            (slug, lint)
+        })
+        .collect();
+
+    let mut linter = Linter::with_options(reporter, timed);
+
+    if opts.no_default_lints {
+        linter = linter.clear_lints();
+    }
+
+    for allow in &opts.allow {
+        linter = linter.allow(allow);
+    }
+
+    for (slug, lint) in named(&opts.warn)? {
+        let lint = Timed::new(&slug, lint, spent);
+        linter = linter.warn(slug, lint);
+    }
+
+    for (slug, lint) in named(&opts.deny)? {
+        let lint = Timed::new(&slug, lint, spent);
+        linter = linter.deny(slug, lint);
+    }
+
+    Ok(linter)
+}
+
+/// Default lints called `names` (from `--warn` or `--deny`.)
+fn named(names: &[String]) -> Result<Vec<(String, DefaultLint<String>)>, Error> {
+    let mut defaults = DefaultOptions::<String>::default().lints;
+    let mut lints = Vec::with_capacity(names.len());
+    for name in names {
+        let (slug, lint) = defaults
+            .remove_entry(name.as_str())
+            .context(NoLintSnafu { name })?;
+        lints.extend(lint.into_lint().map(|l| (slug, l)));
+    }
+    Ok(lints)
+}
+
+async fn lint_file(
+    source: PathBuf,
+    options: DefaultOptions<String>,
+    opts: CmdArgs,
+) -> Result<Linted, Error> {
+    let spent = Spent::default();
+    let start = Instant::now();
+
+    let reporter = linter(Collect::default(), options, &opts, &spent)?
+        .check_file(&source)
+        .run()
+        .await?;
+
+    Ok(Linted {
+        messages: reporter.0.into_inner(),
+        elapsed: start.elapsed(),
+        spent: spent.take(),
+    })
+}
+
+/// Where lint configuration comes from.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// The theme's eipw configuration (or the one given with `--config`.)
+    pub path: PathBuf,
+
+    /// Configuration for individual proposals, from `Build.toml`.
+    pub overrides: LintOverrides,
+}
+
+/// Options for linting `source`: `base`, unless its proposal has overrides
+/// (from `Build.toml`, then a sidecar `eipw.toml` next to its `index.md`) to
+/// merge over the `theme` configuration.
+async fn file_options(
+    theme: &Figment,
+    base: &DefaultOptions<String>,
+    settings: &Settings,
+    repo_dir: &Path,
+    source: &Path,
+    opts: &CmdArgs,
+) -> Result<DefaultOptions<String>, Error> {
+    let relative = source.strip_prefix(repo_dir).unwrap_or(source);
+    let mut figment = theme.clone();
+    let mut overridden = false;
+
+    if let Some(number) = changed::proposal_number(relative.to_path_buf()) {
+        let tables = settings
+            .overrides
+            .iter()
+            .filter(|(k, _)| k.parse::<u64>().ok() == Some(number));
+        for (_, table) in tables {
+            figment = figment.admerge(Serialized::defaults(table));
+            overridden = true;
+        }
+    }
+
+    if source.file_name() == Some(OsStr::new("index.md")) {
+        let sidecar = source.with_file_name(OVERRIDES_FILE);
+        let exists = tokio::fs::try_exists(&sidecar)
+            .await
+            .context(FsSnafu { path: &sidecar })?;
+        if exists {
+            figment = figment.admerge(Toml::file_exact(sidecar));
+            overridden = true;
+        }
+    }
+
+    if !overridden {
+        return Ok(base.clone());
+    }
+
+    debug!(
+        "applying lint overrides for `{}`",
+        relative.to_string_lossy()
+    );
+
+    let config: Config = figment
+        .merge(Serialized::global("command", opts))
+        .extract()
+        .context(OverrideSnafu { path: relative })?;
+
+    Ok(config.eipw)
+}
+
+/// Location of the theme's eipw configuration, fetching the theme into the
+/// cache if necessary.
+pub fn theme_config(theme: &Theme, cache: &Cache) -> Result<PathBuf, Error> {
+    let mut config_path = cache.repo(theme.repository.as_str(), &theme.commit)?;
+
+    config_path.push("config");
+    config_path.push("eipw.toml");
+
+    Ok(config_path)
+}
+
+pub fn eipw(
+    settings: &Settings,
     root_dir: &Path,
     repo_dir: &Path,
     changed_paths: Vec<PathBuf>,
+    changed_lines: Option<&ChangedLines>,
     opts: CmdArgs,
+    output: &Output,
 ) -> Result<(), Error> {
     if opts.no_lint {
         return Ok(());
     }
 
-    let mut stdout = std::io::stdout();
+    let mut builder = if opts.jobs.get() == 1 {
+        tokio::runtime::Builder::new_current_thread()
+    } else {
+        let mut builder = tokio::runtime::Builder::new_multi_thread();
+        builder.worker_threads(opts.jobs.get());
+        builder
+    };
 
-    let mut config_path = cache.repo(theme_repo, theme_rev)?;
+    let runtime = builder.enable_all().build().context(RuntimeSnafu)?;
+
+    runtime.block_on(eipw_async(
+        settings,
+        root_dir,
+        repo_dir,
+        changed_paths,
+        changed_lines,
+        opts,
+        output,
+    ))
+}
 
-    config_path.push("config");
-    config_path.push("eipw.toml");
+/// Lint the files selected by `opts` in the root checkout, as they are.
+pub fn checkout(settings: &Settings, root_path: &Path, opts: CmdArgs) -> Result<(), Error> {
+    let output = Output::new(&opts, root_path)?;
+    let result = eipw(
+        settings,
+        root_path,
+        root_path,
+        Vec::new(),
+        None,
+        opts,
+        &output,
+    );
+    output.finish();
+    result
+}
 
-    let toml_file = Toml::file_exact(&config_path);
+/// Read the eipw configuration, with the command line on top.
+///
+/// Returns the configuration (without the command line) that per-proposal
+/// overrides apply to, and the combined configuration.
+fn load_config(settings: &Settings, opts: CmdArgs) -> Result<(Figment, Config), Error> {
+    let toml_file = Toml::file_exact(&settings.path);
 
     let file_version = Figment::new()
         .merge(&toml_file)
@@ -288,27 +945,45 @@ pub async fn eipw(
 
     version_cmp(file_version, application_version)?;
 
-    let config: Config = Figment::new()
+    let theme = Figment::new()
         .merge(DefaultOptions::<String>::figment())
-        .merge(toml_file)
+        .merge(toml_file);
+
+    let config: Config = theme
+        .clone()
         .merge(Serialized::global("command", opts))
         .extract()
         .context(ConfigSnafu)?;
 
-    let opts = config.command;
+    for key in settings.overrides.keys() {
+        if key.parse::<u64>().is_err() {
+            warn!("ignoring lint overrides for `{key}` (not a proposal number)");
+        }
+    }
 
-    let repo_dir = tokio::fs::canonicalize(repo_dir)
-        .await
-        .context(FsSnafu { path: repo_dir })?;
+    Ok((theme, config))
+}
 
-    let paths = if opts.sources.is_empty() {
+/// Files to lint in `repo_dir` (which must be canonical): the `sources` from
+/// the command line (relative to `root_dir`), every proposal for `--all`, or
+/// otherwise the `changed_paths`.
+async fn resolve_sources(
+    root_dir: &Path,
+    repo_dir: &Path,
+    changed_paths: Vec<PathBuf>,
+    sources: &[PathBuf],
+    all: bool,
+) -> Result<Vec<PathBuf>, Error> {
+    let paths = if all {
+        vec![repo_dir.join(CONTENT_DIR)]
+    } else if sources.is_empty() {
         changed_paths
     } else {
         let root_dir = tokio::fs::canonicalize(root_dir)
             .await
             .context(FsSnafu { path: root_dir })?;
-        let mut repo_relative_sources = Vec::with_capacity(opts.sources.len());
-        for source in &opts.sources {
+        let mut repo_relative_sources = Vec::with_capacity(sources.len());
+        for source in sources {
             let root_relative_source = root_dir.join(source);
             let full_source = tokio::fs::canonicalize(&root_relative_source)
                 .await
@@ -330,68 +1005,268 @@ pub async fn eipw(
         repo_relative_sources
     };
 
-    let sources = collect_sources(paths).await?;
+    let mut sources = collect_sources(paths).await?;
 
-    let reporter = match opts.format {
-        Format::Json => EitherReporter::Json(Json::default()),
-        Format::Text => EitherReporter::Text(Text::default()),
-        Format::GitHub => EitherReporter::GitHub(crate::github::Reporter {
-            root: repo_dir.to_str().expect("repository dir not UTF-8").into(),
-        }),
-    };
+    if all {
+        // Skip `_index.md` and friends.
+        sources.retain(|s| {
+            s.strip_prefix(repo_dir)
+                .map(|r| changed::is_proposal_path(r.to_path_buf()))
+                .unwrap_or(false)
+        });
+        sources.sort();
+    }
 
-    let reporter = AdditionalHelp::new(reporter, |t: &str| {
-        Ok(format!("see https://ethereum.github.io/eipw/{}/", t))
-    });
-    let reporter = Count::new(reporter);
+    Ok(sources)
+}
 
-    let mut linter = Linter::with_options(reporter, config.eipw);
+/// Lints enabled for a file with the given `options`, after `--allow` and
+/// friends.
+fn enabled(
+    options: DefaultOptions<String>,
+    opts: &CmdArgs,
+) -> Result<Vec<DefaultLint<String>>, Error> {
+    let mut lints: HashMap<_, _> = if opts.no_default_lints {
+        HashMap::new()
+    } else {
+        options
+            .lints
+            .into_iter()
+            .filter_map(|(slug, lint)| Some((slug, lint.into_lint()?)))
+            .collect()
+    };
 
-    if opts.no_default_lints {
-        linter = linter.clear_lints();
+    for allow in &opts.allow {
+        lints.remove(allow);
     }
 
-    for allow in opts.allow {
-        linter = linter.allow(&allow);
+    lints.extend(named(&opts.warn)?);
+    lints.extend(named(&opts.deny)?);
+
+    Ok(lints.into_values().collect())
+}
+
+/// A file selected for linting.
+#[derive(Debug)]
+pub struct Source {
+    pub path: PathBuf,
+
+    /// Lints enabled for this file, after overrides and command-line options.
+    pub lints: Vec<DefaultLint<String>>,
+}
+
+/// Files in `root_dir` selected by `opts`, with the lints enabled for each.
+pub fn sources(settings: &Settings, root_dir: &Path, opts: CmdArgs) -> Result<Vec<Source>, Error> {
+    let (theme, config) = load_config(settings, opts)?;
+    let opts = config.command;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context(RuntimeSnafu)?;
+
+    runtime.block_on(async {
+        let root_dir = tokio::fs::canonicalize(root_dir)
+            .await
+            .context(FsSnafu { path: root_dir })?;
+
+        let sources =
+            resolve_sources(&root_dir, &root_dir, Vec::new(), &opts.sources, opts.all).await?;
+
+        let mut output = Vec::with_capacity(sources.len());
+        for source in sources {
+            let options =
+                file_options(&theme, &config.eipw, settings, &root_dir, &source, &opts).await?;
+            let lints = enabled(options, &opts)?;
+            output.push(Source {
+                path: source,
+                lints,
+            });
+        }
+
+        Ok(output)
+    })
+}
+
+async fn eipw_async(
+    settings: &Settings,
+    root_dir: &Path,
+    repo_dir: &Path,
+    changed_paths: Vec<PathBuf>,
+    changed_lines: Option<&ChangedLines>,
+    opts: CmdArgs,
+    output: &Output,
+) -> Result<(), Error> {
+    let (theme, config) = load_config(settings, opts)?;
+    let opts = config.command;
+
+    let repo_dir = tokio::fs::canonicalize(repo_dir)
+        .await
+        .context(FsSnafu { path: repo_dir })?;
+
+    let sources =
+        resolve_sources(root_dir, &repo_dir, changed_paths, &opts.sources, opts.all).await?;
+
+    if sources.is_empty() {
+        info!("no proposals to lint");
+        return Ok(());
     }
 
-    if !opts.warn.is_empty() {
-        let defaults = DefaultOptions::<String>::default();
-        let mut lints: HashMap<_, _> = defaults.lints;
-        for warn in opts.warn {
-            let (k, v) = lints
-                .remove_entry(warn.as_str())
-                .context(NoLintSnafu { name: warn })?;
-            linter = linter.warn(k, v.into_lint().unwrap());
+    let mut baseline = match &opts.baseline {
+        Some(path) => Some(Baseline::load(path, opts.write_baseline)?),
+        None => None,
+    };
+
+    // Each file gets its own linter, since they can't be shared between
+    // threads. Results are reported in order, regardless of which file
+    // finishes first.
+    let mut results = Vec::with_capacity(sources.len());
+    if opts.jobs.get() == 1 {
+        for source in sources.iter().progress_ext("Lint") {
+            interrupt::check()?;
+            let options =
+                file_options(&theme, &config.eipw, settings, &repo_dir, source, &opts).await?;
+            results.push(lint_file(source.clone(), options, opts.clone()).await);
+        }
+    } else {
+        let permits = Arc::new(Semaphore::new(opts.jobs.get()));
+        let mut tasks = Vec::with_capacity(sources.len());
+        for source in &sources {
+            let permit = permits.clone().acquire_owned().await.unwrap();
+            interrupt::check()?;
+            let handle = Handle::current();
+            let options =
+                file_options(&theme, &config.eipw, settings, &repo_dir, source, &opts).await?;
+            let source = source.clone();
+            let opts = opts.clone();
+            tasks.push(tokio::task::spawn_blocking(move || {
+                let _permit = permit;
+                // Lint errors can't cross threads, so they're detached first.
+                handle
+                    .block_on(lint_file(source, options, opts))
+                    .map_err(|e| Detached::new(&e))
+            }));
+        }
+
+        let tasks = sources.iter().zip(tasks).progress_ext("Lint");
+        for (source, task) in tasks {
+            let result = task.await.expect("lint task panicked");
+            results.push(result.context(ThreadSnafu { path: source }));
         }
     }
 
-    if !opts.deny.is_empty() {
-        let defaults = DefaultOptions::<String>::default();
-        let mut lints: HashMap<_, _> = defaults.lints;
-        for deny in opts.deny {
-            let (k, v) = lints
-                .remove_entry(deny.as_str())
-                .context(NoLintSnafu { name: deny })?;
-            linter = linter.deny(k, v.into_lint().unwrap());
+    let reporter = AdditionalHelp::new(&output.reporter, |t: &str| {
+        Ok(format!("see {}", help_url(t)))
+    });
+    let reporter = Count::new(Summary::new(reporter));
+    let mut timings = Timings::default();
+    let mut files = Vec::with_capacity(sources.len());
+    let mut n_unchanged = 0;
+    let mut n_known = 0;
+    let mut recorded = Vec::new();
+
+    for (source, result) in sources.iter().zip(results) {
+        let linted = result?;
+
+        for message in linted.messages {
+            if let Some(baseline) = &mut baseline {
+                let entry = baseline::Entry::new(&message, &repo_dir);
+                if opts.write_baseline {
+                    recorded.push(entry);
+                    continue;
+                }
+                if baseline.suppress(&entry) {
+                    n_known += 1;
+                    continue;
+                }
+            }
+
+            if let Some(changed) = changed_lines {
+                if !touches(&message, &repo_dir, changed) {
+                    n_unchanged += 1;
+                    continue;
+                }
+            }
+
+            reporter.report(message).context(ReportSnafu)?;
         }
+
+        output.ran(source, &linted.spent);
+
+        let relative = source.strip_prefix(&repo_dir).unwrap_or(source);
+        timings.push(relative.to_path_buf(), linted.elapsed, linted.spent);
+        files.push(relative.to_path_buf());
     }
 
-    for source in sources.iter().progress_ext("Lint") {
-        linter = linter.check_file(source);
+    if n_unchanged > 0 {
+        info!("not reporting {n_unchanged} problem(s) on unchanged lines");
     }
 
-    let reporter = linter.run().await?;
+    if let (Some(baseline), Some(path)) = (baseline, &opts.baseline) {
+        if opts.write_baseline {
+            info!(
+                "recording {} problem(s) in `{}`",
+                recorded.len(),
+                path.to_string_lossy()
+            );
+            baseline.write(path, &files, recorded)?;
+        } else {
+            if n_known > 0 {
+                info!("not reporting {n_known} known problem(s)");
+            }
+            let n_fixed = baseline.unmatched(&files);
+            if n_fixed > 0 {
+                info!("{n_fixed} known problem(s) are fixed (update the baseline with `--write-baseline`)");
+            }
+        }
+    }
+
+    *output.linted.borrow_mut() = report::Lint {
+        files,
+        counts: reporter.counts().into(),
+    };
+
+    if let Some(format) = opts.timings {
+        timings.print(format);
+    }
 
     let n_errors = reporter.counts().error;
 
-    match reporter.into_inner().into_inner() {
-        EitherReporter::Json(j) => serde_json::to_writer_pretty(&stdout, &j).unwrap(),
-        EitherReporter::Text(t) => write!(stdout, "{}", t.into_inner()).unwrap(),
-        EitherReporter::GitHub(_) => (),
+    if opts.all {
+        reporter.into_inner().print();
     }
 
     ensure!(n_errors == 0, FailedSnafu { n_errors });
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[derive(Parser)]
+    struct Args {
+        #[command(flatten)]
+        eipw: CmdArgs,
+    }
+
+    #[test]
+    fn reload_does_not_write_baseline() {
+        let args = Args::parse_from([
+            "serve",
+            "--baseline=baseline.json",
+            "--write-baseline",
+            "--timings",
+            "content/00001.md",
+        ]);
+
+        let reload = args.eipw.for_reload();
+        assert!(!reload.write_baseline);
+        assert!(reload.timings.is_none());
+        assert!(!reload.has_sources());
+        assert_eq!(reload.baseline, Some(PathBuf::from("baseline.json")));
+    }
+}
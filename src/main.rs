@@ -4,32 +4,48 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
+mod baseline;
 mod cache;
 mod changed;
 mod cli;
 mod config;
 mod context;
 mod find_root;
+mod fix;
+mod fmt;
 mod git;
 mod github;
+mod incremental;
+mod interrupt;
+mod junit;
 mod layout;
 mod lint;
 mod markdown;
+mod migrate;
+mod new;
 mod print;
 mod progress;
+mod rdjson;
+mod renumber;
+mod report;
+mod sarif;
+mod span;
+mod timings;
+mod watch;
 mod zola;
 
 use std::path::{Path, PathBuf};
 
 use clap::Parser;
 use fslock::LockFile;
-use log::{debug, info};
-use snafu::{Report, ResultExt, Whatever};
+use log::{debug, info, warn};
+use snafu::{whatever, Report, ResultExt, Whatever};
 
 use crate::{
     cli::{Args, Operation},
     config::{Manifest, RepositoryUse},
-    layout::{BUILD_DIR, CONTENT_DIR, OUTPUT_DIR, REPO_DIR},
+    layout::{BUILD_DIR, CONTENT_DIR, OUTPUT_DIR, PREPROCESS_DIR, REPO_DIR},
+    report::{BuildReport, Outcome},
 };
 
 fn lock(build_path: &Path) -> Result<LockFile, Whatever> {
@@ -59,12 +75,32 @@ fn make_build_dir(root: &Path) -> Result<PathBuf, Whatever> {
     Ok(build_path)
 }
 
+/// What a build needs from the build repository and lint configuration.
+struct Assembled {
+    cache: cache::Cache,
+    lint_settings: lint::Settings,
+    output: lint::Output,
+    last_modified: git::LastModified,
+    changed_files: Vec<PathBuf>,
+    changed_lines: Option<git::ChangedLines>,
+}
+
 #[derive(Debug)]
 struct Prepared {
     cache: cache::Cache,
+    root_path: PathBuf,
+    build_path: PathBuf,
     repo_path: PathBuf,
     output_path: PathBuf,
     manifest: Manifest,
+    eipw: lint::CmdArgs,
+    lint_settings: lint::Settings,
+    output: lint::Output,
+    last_modified: git::LastModified,
+    report: BuildReport,
+
+    /// Whether `report` is written out (for `build` and `check`.)
+    write_report: bool,
 }
 
 impl Prepared {
@@ -73,22 +109,88 @@ impl Prepared {
         manifest: Manifest,
         root_path: PathBuf,
         build_path: PathBuf,
+        git_options: git::Options,
+        write_report: bool,
     ) -> Result<Self, Whatever> {
-        zola::find_zola().whatever_context("unable to find suitable zola binary")?;
-
         let repo_path = build_path.join(REPO_DIR);
-        let content_path = repo_path.join(CONTENT_DIR);
         let output_path = build_path.join(OUTPUT_DIR);
 
+        let mut report = BuildReport::new(manifest.theme.clone());
+        let assembled = match Self::assemble(
+            &eipw,
+            &manifest,
+            &root_path,
+            &repo_path,
+            git_options,
+            &mut report,
+        ) {
+            Ok(a) => a,
+            Err(e) => {
+                if write_report {
+                    write_abandoned(&report, &build_path);
+                }
+                return Err(e);
+            }
+        };
+
+        let mut prepared = Prepared {
+            manifest,
+            cache: assembled.cache,
+            root_path,
+            build_path,
+            repo_path,
+            output_path,
+            eipw,
+            lint_settings: assembled.lint_settings,
+            output: assembled.output,
+            last_modified: assembled.last_modified,
+            report,
+            write_report,
+        };
+
+        // Once there's output, it (and the build report) is written out
+        // however the build ends.
+        match prepared
+            .lint_and_preprocess(assembled.changed_files, assembled.changed_lines.as_ref())
+        {
+            Ok(()) => Ok(prepared),
+            Err(e) => {
+                prepared.abandon();
+                Err(e)
+            }
+        }
+    }
+
+    /// Find zola, assemble the build repository, and set up linting, timing
+    /// each step in `report`.
+    fn assemble(
+        eipw: &lint::CmdArgs,
+        manifest: &Manifest,
+        root_path: &Path,
+        repo_path: &Path,
+        git_options: git::Options,
+        report: &mut BuildReport,
+    ) -> Result<Assembled, Whatever> {
+        // The outcome is set before each step, so it's what gets reported if
+        // that step fails.
+        report.outcome = Outcome::ZolaFailed;
+        zola::find_zola().whatever_context("unable to find suitable zola binary")?;
+        report.step("zola");
+
+        report.outcome = Outcome::GitFailed;
         let repository_use = RepositoryUse::try_from(manifest.clone())
             .whatever_context("cannot identify repository use")?;
 
-        let both = git::Fresh::new(&root_path, &repo_path, repository_use)
+        let both = git::Fresh::new(root_path, repo_path, repository_use, git_options)
             .whatever_context("initializing build repo")?
             .clone_src()
             .whatever_context("cloning source repo")?
             .fetch_upstream()
             .whatever_context("fetching upstream repo")?;
+        report
+            .fetched(&both)
+            .whatever_context("unable to find commits to build")?;
+        report.step("fetch");
 
         let changed_files: Vec<_> = both
             .changed_files()
@@ -98,114 +200,391 @@ impl Prepared {
             .map(|p| repo_path.join(p))
             .collect();
 
-        both.merge()
+        let changed_lines = if eipw.changed_lines() {
+            Some(
+                both.changed_lines()
+                    .whatever_context("unable to list changed lines")?,
+            )
+        } else {
+            None
+        };
+        report.step("diff");
+
+        let merged = both
+            .merge()
             .whatever_context("unable to merge ERC/EIP repositories")?;
+        report.merged(merged);
+        report.step("merge");
 
-        let cache = cache::Cache::open().whatever_context("unable to open cache")?;
+        let last_modified = both
+            .last_modified()
+            .whatever_context("unable to find last modified times")?;
+        report.step("last-modified");
 
-        lint::eipw(
-            manifest.theme.repository.as_str(),
-            &manifest.theme.commit,
-            &cache,
-            &root_path,
-            &repo_path,
-            changed_files,
-            eipw,
-        )
-        .whatever_context("linting failed")?;
+        interrupt::check().whatever_context("build stopped")?;
 
-        markdown::preprocess(&content_path).whatever_context("unable to preprocess markdown")?;
+        report.outcome = Outcome::LintFailed;
+        let cache =
+            cache::Cache::open(git_options.offline).whatever_context("unable to open cache")?;
 
-        Ok(Prepared {
-            manifest,
+        let lint_settings = lint::Settings {
+            path: lint::theme_config(&manifest.theme, &cache)
+                .whatever_context("unable to find eipw configuration")?,
+            overrides: manifest.lint_overrides.clone(),
+        };
+
+        let output =
+            lint::Output::new(eipw, repo_path).whatever_context("unable to set up output")?;
+
+        report.outcome = Outcome::Succeeded;
+        Ok(Assembled {
             cache,
-            repo_path,
-            output_path,
+            lint_settings,
+            output,
+            last_modified,
+            changed_files,
+            changed_lines,
         })
     }
 
+    fn lint_and_preprocess(
+        &mut self,
+        changed_files: Vec<PathBuf>,
+        changed_lines: Option<&git::ChangedLines>,
+    ) -> Result<(), Whatever> {
+        let linted = lint::eipw(
+            &self.lint_settings,
+            &self.root_path,
+            &self.repo_path,
+            changed_files,
+            changed_lines,
+            self.eipw.clone(),
+            &self.output,
+        );
+
+        self.report.step("lint");
+        self.report.lint = self.output.linted();
+
+        if let Err(e) = linted {
+            self.report.outcome = Outcome::LintFailed;
+            return Err(e).whatever_context("linting failed");
+        }
+
+        let preprocessed = self.preprocess();
+        self.report.step("preprocess");
+
+        if preprocessed.is_err() {
+            self.report.outcome = Outcome::PreprocessFailed;
+        }
+        preprocessed
+    }
+
+    fn preprocess(&self) -> Result<(), Whatever> {
+        let store = incremental::Store::open(
+            &self.build_path.join(PREPROCESS_DIR),
+            &self.manifest.theme.commit,
+        )
+        .whatever_context("unable to open preprocessed output")?;
+        markdown::preprocess(
+            &self.repo_path.join(CONTENT_DIR),
+            &self.last_modified,
+            Some(&store),
+        )
+        .whatever_context("unable to preprocess markdown")?;
+        store
+            .save()
+            .whatever_context("unable to save preprocessed output")
+    }
+
+    /// Write out what was found before the build stopped early.
+    fn abandon(self) {
+        self.output.finish();
+        if self.write_report {
+            write_abandoned(&self.report, &self.build_path);
+        }
+    }
+
+    /// Report zola's diagnostics alongside lint results, then write them out
+    /// along with the build report.
+    fn finish(
+        mut self,
+        diagnostics: Vec<zola::Diagnostic>,
+        succeeded: bool,
+    ) -> Result<(), Whatever> {
+        let reported = self.output.zola(&self.root_path, &diagnostics);
+        self.output.finish();
+
+        self.report.step("zola");
+        self.report.zola = diagnostics;
+        if !succeeded {
+            self.report.outcome = Outcome::ZolaFailed;
+        }
+        let written = self.report.write(&self.build_path);
+
+        reported.whatever_context("unable to report zola diagnostics")?;
+        written.whatever_context("unable to write build report")
+    }
+
     fn build(self) -> Result<(), Whatever> {
         let repository_use = RepositoryUse::try_from(self.manifest.clone())
             .whatever_context("cannot identify repository use")?;
-        zola::build(
+        let mut diagnostics = Vec::new();
+        let result = zola::build(
             self.manifest.theme.repository.as_str(),
             &self.manifest.theme.commit,
             &self.cache,
             &self.repo_path,
             &self.output_path,
             repository_use.location.base_url.as_str(),
-        )
-        .whatever_context("zola build failed")?;
-        Ok(())
+            &mut |d| diagnostics.push(d.clone()),
+        );
+        self.finish(diagnostics, result.is_ok())?;
+        result.whatever_context("zola build failed")
     }
 
     fn serve(self) -> Result<(), Whatever> {
-        zola::serve(
+        // Problems found while serving are logged as they happen instead.
+        self.output.finish();
+
+        let _watcher = watch::Watcher::spawn(
+            &self.lint_settings,
+            &self.root_path,
+            &self.repo_path,
+            self.eipw.clone(),
+            self.last_modified.clone(),
+        )
+        .whatever_context("unable to watch for changes")?;
+
+        let result = zola::serve(
             self.manifest.theme.repository.as_str(),
             &self.manifest.theme.commit,
             &self.cache,
             &self.repo_path,
             &self.output_path,
-        )
-        .whatever_context("zola serve failed")?;
-        Ok(())
+            &mut |_| (),
+        );
+
+        match result {
+            // Ctrl-C is the usual way to stop serving.
+            Err(zola::Error::Interrupt {
+                source: interrupt::Error::Interrupted { .. },
+            }) => Ok(()),
+            other => other.whatever_context("zola serve failed"),
+        }
     }
 
     fn check(self) -> Result<(), Whatever> {
-        zola::check(
+        let mut diagnostics = Vec::new();
+        let result = zola::check(
             self.manifest.theme.repository.as_str(),
             &self.manifest.theme.commit,
             &self.cache,
             &self.repo_path,
-        )
-        .whatever_context("zola check failed")?;
-        Ok(())
+            &mut |d| diagnostics.push(d.clone()),
+        );
+        self.finish(diagnostics, result.is_ok())?;
+        result.whatever_context("zola check failed")
     }
 }
 
-fn run() -> Result<(), Whatever> {
-    let args = Args::parse();
-    if let Operation::Print { print } = args.operation {
-        print::print(print);
-        return Ok(());
+/// Write the report for a build that stopped early, which already has an
+/// error of its own to return.
+fn write_abandoned(report: &BuildReport, build_path: &Path) {
+    if let Err(e) = report.write(build_path) {
+        warn!("unable to write build report: {}", Report::from_error(e));
     }
+}
 
-    let root_path = context::root(&args)?;
-
+/// Where lint configuration for the root checkout comes from: `config`, or
+/// the theme in the manifest, with overrides from the manifest either way.
+fn lint_settings(
+    root_path: &Path,
+    config: Option<PathBuf>,
+    offline: bool,
+) -> Result<lint::Settings, Whatever> {
     let manifest_path = root_path.join(config::MANIFEST_FILE);
-    let manifest = Manifest::load(&manifest_path).whatever_context("unable to read manifest")?;
+    let manifest = match Manifest::load(&manifest_path) {
+        Ok(m) => Some(m),
+        // `--config` doesn't need the manifest, but overrides in it still
+        // apply when there is one.
+        Err(e) if config.is_some() => {
+            debug!("linting without manifest: {}", Report::from_error(e));
+            None
+        }
+        Err(e) => return Err(e).whatever_context("unable to read manifest"),
+    };
+
+    let path = match (config, &manifest) {
+        (Some(c), _) => c,
+        (None, Some(manifest)) => {
+            let cache = cache::Cache::open(offline).whatever_context("unable to open cache")?;
+            lint::theme_config(&manifest.theme, &cache)
+                .whatever_context("unable to find eipw configuration")?
+        }
+        (None, None) => unreachable!("manifest is required without `--config`"),
+    };
 
-    let build_path = make_build_dir(&root_path)?;
+    Ok(lint::Settings {
+        path,
+        overrides: manifest.map(|m| m.lint_overrides).unwrap_or_default(),
+    })
+}
 
-    let mut lock_file = lock(&build_path)?;
+/// Lint proposals in the root checkout as they are, without building anything.
+fn lint(
+    root_path: &Path,
+    config: Option<PathBuf>,
+    eipw: lint::CmdArgs,
+    offline: bool,
+) -> Result<(), Whatever> {
+    eipw.require_sources("lint")?;
+
+    if eipw.changed_lines() {
+        whatever!("`--changed-lines` needs the upstream repository (use `check` instead)");
+    }
 
-    match args.operation {
-        Operation::Print { .. } => unreachable!(),
+    let settings = lint_settings(root_path, config, offline)?;
+    lint::checkout(&settings, root_path, eipw).whatever_context("linting failed")
+}
+
+fn run() -> Result<(), Whatever> {
+    let Args {
+        root,
+        worktree,
+        offline,
+        operation,
+    } = Args::parse();
+    interrupt::init().whatever_context("unable to install ctrl-c handler")?;
+
+    let root_path = || context::root(root.as_deref());
+    let git_options = git::Options { worktree, offline };
+
+    match operation {
+        Operation::Print { print } => {
+            print::print(print);
+            Ok(())
+        }
+        Operation::Lint { config, eipw } => lint(&root_path()?, config, eipw, offline),
+        Operation::Fix {
+            dry_run,
+            config,
+            eipw,
+        } => {
+            let root_path = root_path()?;
+            let settings = lint_settings(&root_path, config, offline)?;
+            fix::run(&settings, &root_path, eipw.into(), dry_run)
+        }
+        Operation::Fmt {
+            check,
+            config,
+            eipw,
+        } => {
+            let root_path = root_path()?;
+            let settings = lint_settings(&root_path, config, offline)?;
+            fmt::run(&root_path, &settings, eipw.into(), check)
+        }
+        Operation::Migrate => migrate::run(&root_path()?),
+        Operation::Renumber { old, new } => renumber::run(&root_path()?, old, new),
+        Operation::New { new, config, eipw } => {
+            if eipw.has_sources() {
+                whatever!("`new` only lints the proposal it creates");
+            }
+            let root_path = root_path()?;
+            let created = new::create(&root_path, &new)?;
+            lint(
+                &root_path,
+                config,
+                eipw.with_sources(vec![created]),
+                offline,
+            )
+        }
         Operation::Clean => {
+            let root_path = root_path()?;
+            let manifest_path = root_path.join(config::MANIFEST_FILE);
+            Manifest::load(&manifest_path).whatever_context("unable to read manifest")?;
+
+            let build_path = make_build_dir(&root_path)?;
+            let mut lock_file = lock(&build_path)?;
             // TODO: There's a race condition here. Maybe we move the lockfile to the repository
             //       root?
             lock_file
                 .unlock()
                 .whatever_context("unable to unlock build directory")?;
-            std::fs::remove_dir_all(build_path)
-                .whatever_context("unable to remove build directory")?;
-            return Ok(());
+            std::fs::remove_dir_all(build_path).whatever_context("unable to remove build directory")
         }
         Operation::Check { eipw } => {
-            Prepared::prepare(eipw, manifest, root_path, build_path)?.check()?;
+            let root_path = root_path()?;
+            with_lock(&root_path, |manifest, build_path| {
+                Prepared::prepare(
+                    eipw,
+                    manifest,
+                    root_path.clone(),
+                    build_path,
+                    git_options,
+                    true,
+                )?
+                .check()
+            })
         }
         Operation::Build { eipw } => {
-            Prepared::prepare(eipw, manifest, root_path, build_path)?.build()?;
+            let root_path = root_path()?;
+            with_lock(&root_path, |manifest, build_path| {
+                Prepared::prepare(
+                    eipw,
+                    manifest,
+                    root_path.clone(),
+                    build_path,
+                    git_options,
+                    true,
+                )?
+                .build()
+            })
         }
         Operation::Serve { eipw } => {
-            Prepared::prepare(eipw, manifest, root_path, build_path)?.serve()?;
+            let root_path = root_path()?;
+            with_lock(&root_path, |manifest, build_path| {
+                Prepared::prepare(
+                    eipw,
+                    manifest,
+                    root_path.clone(),
+                    build_path,
+                    git_options,
+                    false,
+                )?
+                .serve()
+            })
         }
         Operation::Changed { all, format } => {
-            let repository_use = RepositoryUse::try_from(manifest)
-                .whatever_context("cannot identify repository use")?;
-            changed::run(&root_path, &build_path, repository_use, all, &format)?;
+            let root_path = root_path()?;
+            with_lock(&root_path, |manifest, build_path| {
+                let repository_use = RepositoryUse::try_from(manifest)
+                    .whatever_context("cannot identify repository use")?;
+                changed::run(
+                    &root_path,
+                    &build_path,
+                    repository_use,
+                    git_options,
+                    all,
+                    &format,
+                )
+            })
         }
     }
+}
+
+/// Run `f` with the manifest and the build directory, while holding the build
+/// directory's lock.
+fn with_lock<F>(root_path: &Path, f: F) -> Result<(), Whatever>
+where
+    F: FnOnce(Manifest, PathBuf) -> Result<(), Whatever>,
+{
+    let manifest_path = root_path.join(config::MANIFEST_FILE);
+    let manifest = Manifest::load(&manifest_path).whatever_context("unable to read manifest")?;
+
+    let build_path = make_build_dir(root_path)?;
+    let mut lock_file = lock(&build_path)?;
+
+    f(manifest, build_path)?;
 
     lock_file
         .unlock()
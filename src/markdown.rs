@@ -18,17 +18,21 @@ use pulldown_cmark::{CodeBlockKind, CowStr, Event, Options, Parser, Tag, TagEnd}
 
 use pulldown_cmark_to_cmark::cmark;
 
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
 use regex::Regex;
 
 use serde::{Deserialize, Serialize};
 
 use std::collections::HashMap;
 use std::ffi::OsStr;
+use std::fmt;
 use std::fs::read_to_string;
-use std::io::Write;
+use std::io::ErrorKind;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 
-use snafu::{whatever, OptionExt, ResultExt, Whatever};
+use snafu::{whatever, Backtrace, OptionExt, ResultExt, Snafu};
 
 use toml::Value;
 
@@ -38,10 +42,24 @@ use walkdir::WalkDir;
 
 use iref::IriRefBuf;
 
-use crate::progress::ProgressIteratorExt;
+use crate::git::LastModified;
+use crate::incremental::{Link, Store};
+use crate::interrupt;
+use crate::layout::OVERRIDES_FILE;
+use crate::progress::{ParallelProgressIteratorExt, ProgressIteratorExt};
+
+/// Like [`snafu::Whatever`], but can be sent between threads.
+#[derive(Debug, Snafu)]
+#[snafu(whatever, display("{message}"))]
+pub struct Error {
+    #[snafu(source(from(Box<dyn std::error::Error + Send + Sync>, Some)))]
+    source: Option<Box<dyn std::error::Error + Send + Sync>>,
+    message: String,
+    backtrace: Backtrace,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
-struct Author {
+pub(crate) struct Author {
     name: String,
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -51,6 +69,20 @@ struct Author {
     email: Option<String>,
 }
 
+impl fmt::Display for Author {
+    /// Formats the author the way proposals list them.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(github) = &self.github {
+            write!(f, " (@{github})")?;
+        }
+        if let Some(email) = &self.email {
+            write!(f, " <{email}>")?;
+        }
+        Ok(())
+    }
+}
+
 impl From<Author> for Value {
     fn from(value: Author) -> Self {
         // TODO: Hacky way to implement this conversion...
@@ -137,51 +169,85 @@ impl Default for FrontMatter {
     }
 }
 
-fn last_modified(p: &Path) -> Result<Datetime, Whatever> {
-    // TODO: Replace this with `git2`
-    let mut command = std::process::Command::new("git");
-    command
-        .current_dir(p.parent().unwrap())
-        .arg("log")
-        .arg("-1")
-        .arg("--pretty=format:%ct")
-        .arg("--")
-        .arg(p.file_name().unwrap());
-
-    let output = command
-        .output()
-        .with_whatever_context(|e| format!("failed to execute {:?}: {e}", command))?;
-
-    if !output.status.success() {
-        let err_str = std::str::from_utf8(&output.stderr).unwrap_or("<non-utf-8>");
-        whatever!("command {:?} failed: {err_str}", command);
-    }
-
-    let date_str = std::str::from_utf8(&output.stdout)
-        .with_whatever_context(|e| format!("command {:?} output not UTF-8: {e}", command))?;
-
-    let unix: i64 = date_str.parse().with_whatever_context(|e| {
-        let err_str = std::str::from_utf8(&output.stderr).unwrap_or("<non-utf-8>");
+fn last_modified(
+    root: &Path,
+    path: &Path,
+    last_modified: &LastModified,
+) -> Result<Option<Datetime>, Error> {
+    let relative = path.strip_prefix(root).with_whatever_context(|_| {
         format!(
-            "unable to parse timestamp `{date_str}` from {:?}: {e}\n{err_str}",
-            command
+            "`{}` is not in `{}`",
+            path.to_string_lossy(),
+            root.to_string_lossy()
         )
     })?;
 
-    let date_time = DateTime::from_timestamp(unix, 0).unwrap();
+    let unix = match last_modified.get(relative) {
+        Some(u) => u,
+        None => {
+            debug!("no commit touches `{}`", relative.to_string_lossy());
+            return Ok(None);
+        }
+    };
+
+    let date_time = DateTime::from_timestamp(unix, 0)
+        .with_whatever_context(|| format!("commit time `{unix}` out of range"))?;
+
+    Ok(Some(date_time.to_rfc3339().parse().unwrap()))
+}
 
-    Ok(date_time.to_rfc3339().parse().unwrap())
+fn render_file(front_matter: FrontMatter, body: &str) -> String {
+    format!(
+        "+++\n{}\n+++\n{}\n",
+        toml::to_string(&front_matter).unwrap(),
+        body
+    )
 }
 
-fn write_file(path: &Path, front_matter: FrontMatter, body: &str) -> std::io::Result<()> {
-    let mut output = std::fs::OpenOptions::new()
-        .truncate(true)
-        .write(true)
-        .open(path)?;
-    writeln!(output, "+++")?;
-    writeln!(output, "{}", toml::to_string(&front_matter).unwrap())?;
-    writeln!(output, "+++")?;
-    writeln!(output, "{}", body)?;
+/// Replace `path` with `contents` by renaming a temporary file over it, so
+/// `zola serve` never sees a partially written (or missing) file.
+pub(crate) fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    // Zola ignores `.tmp` files.
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+
+    std::fs::write(&temp, contents)?;
+    std::fs::rename(&temp, path)
+}
+
+/// Replace the file at `path` with the output of `process`, or with output
+/// stored by an earlier build if nothing it depends on has changed.
+fn cached<F>(
+    root: &Path,
+    path: &Path,
+    contents: &str,
+    updated: Option<&Datetime>,
+    store: Option<&Store>,
+    process: F,
+) -> Result<(), Error>
+where
+    F: FnOnce(&mut Vec<Link>) -> Result<String, Error>,
+{
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let key = store.map(|s| s.key(relative, contents.as_bytes(), updated));
+
+    if let (Some(store), Some(key)) = (store, &key) {
+        if let Some(output) = store.get(key, |links| links_valid(root, links)) {
+            debug!("reusing preprocessed `{}`", relative.to_string_lossy());
+            return write_atomic(path, output).whatever_context("couldn't write file");
+        }
+    }
+
+    let mut links = Vec::new();
+    let output = process(&mut links)?;
+    write_atomic(path, &output).whatever_context("couldn't write file")?;
+
+    if let (Some(store), Some(key)) = (store, key) {
+        store
+            .insert(key, links, &output)
+            .whatever_context("unable to store preprocessed output")?;
+    }
+
     Ok(())
 }
 
@@ -197,7 +263,7 @@ lazy_static! {
     static ref RE_NAME: Regex = Regex::new(r"^([^()<>,@]+)$").unwrap();
 }
 
-fn extract_authors(value: &str) -> Result<Vec<Author>, Whatever> {
+pub(crate) fn extract_authors(value: &str) -> Result<Vec<Author>, Error> {
     let mut authors = Vec::new();
     let items = value.split(',').map(|x| x.trim());
     for item in items {
@@ -232,53 +298,102 @@ fn extract_authors(value: &str) -> Result<Vec<Author>, Whatever> {
     Ok(authors)
 }
 
-pub fn preprocess(root_path: &Path) -> Result<(), Whatever> {
+pub fn preprocess(
+    root_path: &Path,
+    last_modified: &LastModified,
+    store: Option<&Store>,
+) -> Result<(), Error> {
     let dir = std::fs::read_dir(root_path).with_whatever_context(|_| {
         format!("could not read directory `{}`", root_path.to_string_lossy())
     })?;
-    let dirs: Vec<_> = dir.collect();
-
-    info!("preprocessing markdown");
-
-    for entry in dirs.into_iter().progress_ext("Markdown") {
-        let entry = entry.with_whatever_context(|_| {
+    let dirs = dir
+        .collect::<Result<Vec<_>, _>>()
+        .with_whatever_context(|_| {
             format!(
                 "could not read directory entry in `{}`",
                 root_path.to_string_lossy()
             )
         })?;
-        let entry_path = entry.path();
 
-        let file_type = entry.file_type().with_whatever_context(|_| {
+    info!("preprocessing markdown");
+
+    // Entries are independent of each other, so process them in parallel.
+    // Every entry runs to completion, and the first error (in directory
+    // order) is the one reported, so failures don't depend on scheduling.
+    let results: Vec<_> = dirs
+        .into_par_iter()
+        .progress_ext("Markdown")
+        .map(|entry| {
+            interrupt::check().whatever_context("preprocessing stopped")?;
+            let path = entry.path();
+            preprocess_entry(root_path, &path, &path, last_modified, store)
+        })
+        .collect();
+
+    results.into_iter().collect()
+}
+
+/// Preprocess a single top-level entry of the content directory (either a
+/// `NNNNN.md` file or a `NNNNN/` directory with its assets.)
+///
+/// Markdown is read from `source_path` (which is `entry_path` itself when
+/// preprocessing in place), and written to `entry_path`. Non-markdown files
+/// aren't copied.
+pub fn preprocess_entry(
+    root_path: &Path,
+    source_path: &Path,
+    entry_path: &Path,
+    last_modified: &LastModified,
+    store: Option<&Store>,
+) -> Result<(), Error> {
+    let file_type = std::fs::symlink_metadata(source_path)
+        .with_whatever_context(|_| {
             format!(
                 "could not get file type for `{}`",
-                entry.path().to_string_lossy()
+                source_path.to_string_lossy()
             )
-        })?;
+        })?
+        .file_type();
 
-        if log_enabled!(Level::Debug) {
-            let relative = match entry_path.strip_prefix(root_path) {
-                Ok(r) => r,
-                Err(_) => &entry_path,
-            };
-            match relative.with_extension("").to_string_lossy().parse::<u64>() {
-                Ok(n) => debug!("preprocessing {}", n),
-                Err(_) => debug!("preprocessing `{}`", relative.to_string_lossy()),
-            }
+    if log_enabled!(Level::Debug) {
+        let relative = match entry_path.strip_prefix(root_path) {
+            Ok(r) => r,
+            Err(_) => entry_path,
+        };
+        match relative.with_extension("").to_string_lossy().parse::<u64>() {
+            Ok(n) => debug!("preprocessing {}", n),
+            Err(_) => debug!("preprocessing `{}`", relative.to_string_lossy()),
         }
+    }
 
-        if file_type.is_dir() {
-            process_eip(root_path, &entry_path.join("index.md"))?;
-            process_assets(root_path, &entry_path)?;
-        } else if entry_path.extension().and_then(OsStr::to_str) == Some("md") {
-            process_eip(root_path, &entry_path)?;
+    if file_type.is_dir() {
+        process_eip(
+            root_path,
+            &source_path.join("index.md"),
+            &entry_path.join("index.md"),
+            last_modified,
+            store,
+        )?;
+        process_assets(root_path, source_path, entry_path, store)?;
+
+        // Overrides are only for linting, and zola would publish them.
+        let overrides = entry_path.join(OVERRIDES_FILE);
+        match std::fs::remove_file(&overrides) {
+            Err(e) if e.kind() != ErrorKind::NotFound => {
+                return Err(e).with_whatever_context(|_| {
+                    format!("could not remove `{}`", overrides.to_string_lossy())
+                });
+            }
+            _ => (),
         }
+    } else if entry_path.extension().and_then(OsStr::to_str) == Some("md") {
+        process_eip(root_path, source_path, entry_path, last_modified, store)?;
     }
 
     Ok(())
 }
 
-fn path_to_at(root: &Path, parent: &Path, input: &str) -> Result<String, Whatever> {
+pub(crate) fn path_to_at(root: &Path, parent: &Path, input: &str) -> Result<String, Error> {
     let croot = std::fs::canonicalize(root).with_whatever_context(|_| {
         format!("could not canonicalize `{}`", root.to_string_lossy())
     })?;
@@ -292,11 +407,40 @@ fn path_to_at(root: &Path, parent: &Path, input: &str) -> Result<String, Whateve
     };
 
     let cchild = canonicalize_md(&child)?;
-    let relative = cchild.strip_prefix(&croot).expect("child not in root");
-    Ok(format!("@/{}", relative.to_str().unwrap()))
+    let relative = cchild.strip_prefix(&croot).with_whatever_context(|_| {
+        format!("`{input}` is outside of `{}`", root.to_string_lossy())
+    })?;
+    let relative = relative
+        .to_str()
+        .with_whatever_context(|| format!("non-UTF-8 in `{}`", relative.to_string_lossy()))?;
+    Ok(format!("@/{relative}"))
+}
+
+/// Like [`path_to_at`], but remember the result so it can be checked again.
+fn resolve(
+    root: &Path,
+    parent: &Path,
+    input: &str,
+    links: &mut Vec<Link>,
+) -> Result<String, Error> {
+    let output = path_to_at(root, parent, input)?;
+    links.push(Link {
+        parent: parent.strip_prefix(root).unwrap_or(parent).to_path_buf(),
+        input: input.to_owned(),
+        output: output.clone(),
+    });
+    Ok(output)
+}
+
+/// Whether every link still resolves to the same place.
+fn links_valid(root: &Path, links: &[Link]) -> bool {
+    links.iter().all(|link| {
+        let parent = root.join(&link.parent);
+        matches!(path_to_at(root, &parent, &link.input), Ok(o) if o == link.output)
+    })
 }
 
-fn canonicalize_md(path: &Path) -> Result<PathBuf, Whatever> {
+fn canonicalize_md(path: &Path) -> Result<PathBuf, Error> {
     let first_error = match std::fs::canonicalize(path) {
         Ok(canon) => return Ok(canon),
         Err(e) => e,
@@ -336,8 +480,9 @@ fn canonicalize_md(path: &Path) -> Result<PathBuf, Whatever> {
 fn fix_links<'a, 'b>(
     root: &'a Path,
     parent: &'a Path,
+    links: &mut Vec<Link>,
     mut e: Event<'b>,
-) -> Result<Event<'b>, Whatever> {
+) -> Result<Event<'b>, Error> {
     match &mut e {
         Event::Start(Tag::Image { dest_url, .. }) | Event::Start(Tag::Link { dest_url, .. }) => {
             let mut iri_ref = IriRefBuf::new(dest_url.clone().into_string())
@@ -354,7 +499,7 @@ fn fix_links<'a, 'b>(
                 return Ok(e);
             }
 
-            let canonicalized = path_to_at(root, parent, iri_ref.path())?;
+            let canonicalized = resolve(root, parent, iri_ref.path(), links)?;
             let path = iref::iri::Path::new(&canonicalized).expect("path is valid IRI");
             iri_ref.set_path(path);
 
@@ -370,7 +515,7 @@ struct RenderCsl {
 }
 
 impl RenderCsl {
-    fn render_csl<'a>(&mut self, event: Event<'a>) -> Result<Option<Event<'a>>, Whatever> {
+    fn render_csl<'a>(&mut self, event: Event<'a>) -> Result<Option<Event<'a>>, Error> {
         let text = match (&mut self.contents, event) {
             (contents @ None, Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref lang))))
                 if lang.as_ref() == "csl-json" =>
@@ -429,19 +574,68 @@ impl RenderCsl {
     }
 }
 
-fn transform_markdown(root: &Path, path: &Path, body: &str) -> Result<String, Whatever> {
+/// Markdown extensions enabled when parsing proposals.
+pub(crate) fn parser_options() -> Options {
     let mut opts = Options::empty();
     opts.insert(Options::ENABLE_TABLES);
     opts.insert(Options::ENABLE_FOOTNOTES);
     opts.insert(Options::ENABLE_STRIKETHROUGH);
     opts.insert(Options::ENABLE_TASKLISTS);
     opts.insert(Options::ENABLE_HEADING_ATTRIBUTES);
+    opts
+}
+
+/// Destinations of the links and images in a proposal, with where each one
+/// appears in `contents`. Links using reference definitions are found through
+/// their definition.
+pub(crate) fn link_destinations(contents: &str) -> Vec<(Range<usize>, String)> {
+    let body = match Preamble::split(contents) {
+        Ok((_, body)) => body,
+        Err(_) => contents,
+    };
+    let base = body.as_ptr() as usize - contents.as_ptr() as usize;
+
+    let parser = Parser::new_ext(body, parser_options());
+
+    let mut spans: Vec<_> = parser
+        .reference_definitions()
+        .iter()
+        .map(|(_, def)| (def.span.clone(), def.dest.to_string()))
+        .collect();
+
+    for (event, span) in parser.into_offset_iter() {
+        if let Event::Start(Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. }) = event {
+            spans.push((span, dest_url.into_string()));
+        }
+    }
 
+    // Destinations with escapes (or from a reference definition, when looking
+    // in the span of a link) don't appear as-is, and are skipped.
+    let mut found: Vec<_> = spans
+        .into_iter()
+        .filter_map(|(span, dest)| {
+            let start = base + span.start + body[span].find(&dest)?;
+            Some((start..start + dest.len(), dest))
+        })
+        .collect();
+
+    found.sort_by_key(|(range, _)| range.start);
+    found.dedup_by_key(|(range, _)| range.start);
+    found
+}
+
+fn transform_markdown(
+    root: &Path,
+    path: &Path,
+    body: &str,
+    links: &mut Vec<Link>,
+) -> Result<String, Error> {
+    let opts = parser_options();
     let parent = path.parent().unwrap();
     let mut csl = RenderCsl { contents: None };
 
     let events = Parser::new_ext(body, opts)
-        .map(|e| fix_links(root, parent, e))
+        .map(|e| fix_links(root, parent, links, e))
         .filter_map(|r| match r {
             Ok(e) => csl.render_csl(e).transpose(),
             err => Some(err),
@@ -456,8 +650,15 @@ fn transform_markdown(root: &Path, path: &Path, body: &str) -> Result<String, Wh
     Ok(output)
 }
 
-fn process_assets(root: &Path, path: &Path) -> Result<(), Whatever> {
-    let canon_root = std::fs::canonicalize(root).whatever_context("could not canonicalize root")?;
+fn process_assets(
+    root: &Path,
+    source: &Path,
+    path: &Path,
+    store: Option<&Store>,
+) -> Result<(), Error> {
+    let source_root = source.parent().unwrap_or(root);
+    let canon_root =
+        std::fs::canonicalize(source_root).whatever_context("could not canonicalize root")?;
     let number_txt = path
         .file_name()
         .with_whatever_context(|| format!("no file name for `{}`", path.to_string_lossy()))?
@@ -469,8 +670,9 @@ fn process_assets(root: &Path, path: &Path) -> Result<(), Whatever> {
     })?;
 
     let assets_dir = path.join("assets");
+    let source_assets_dir = source.join("assets");
 
-    let dir = WalkDir::new(&assets_dir)
+    let dir = WalkDir::new(&source_assets_dir)
         .follow_links(true)
         .into_iter()
         .filter(|e| match e {
@@ -508,74 +710,101 @@ fn process_assets(root: &Path, path: &Path) -> Result<(), Whatever> {
 
     for entry in dirs.into_iter().progress_ext("Assets") {
         let entry = entry.with_whatever_context(|_| {
-            format!("couldn't read entry in `{}`", assets_dir.to_string_lossy())
+            format!(
+                "couldn't read entry in `{}`",
+                source_assets_dir.to_string_lossy()
+            )
         })?;
 
-        let path = entry.path();
-        let contents = read_to_string(path).with_whatever_context(|_| {
-            format!("could not read file `{}`", path.to_string_lossy())
+        let contents = read_to_string(entry.path()).with_whatever_context(|_| {
+            format!("could not read file `{}`", entry.path().to_string_lossy())
         })?;
 
-        let contents = transform_markdown(root, path, &contents).with_whatever_context(|_| {
-            format!(
-                "unable to transform markdown for `{}`",
-                path.to_string_lossy()
-            )
-        })?;
+        let relative = entry.path().strip_prefix(&source_assets_dir).unwrap();
+        let path = &assets_dir.join(relative);
 
-        let relative_path = path.strip_prefix(&assets_dir).unwrap();
-        let relative_path = relative_path.with_file_name(relative_path.file_stem().unwrap());
+        cached(root, path, &contents, None, store, |links| {
+            let contents =
+                transform_markdown(root, path, &contents, links).with_whatever_context(|_| {
+                    format!(
+                        "unable to transform markdown for `{}`",
+                        path.to_string_lossy()
+                    )
+                })?;
 
-        let alias_bases = [
-            PathBuf::from(format!("/assets/eip-{number}/")),
-            PathBuf::from(format!("/assets/erc-{number}/")),
-        ];
+            let relative_path = path.strip_prefix(&assets_dir).unwrap();
+            let relative_path = relative_path.with_file_name(relative_path.file_stem().unwrap());
 
-        let mut aliases = Vec::with_capacity(alias_bases.len());
+            let alias_bases = [
+                PathBuf::from(format!("/assets/eip-{number}/")),
+                PathBuf::from(format!("/assets/erc-{number}/")),
+            ];
 
-        for alias_base in &alias_bases {
-            aliases.push(alias_base.join(&relative_path));
-        }
+            let mut aliases = Vec::with_capacity(alias_bases.len());
 
-        if relative_path.ends_with("README") || relative_path.ends_with("index") {
-            let index_path = relative_path.parent().unwrap();
             for alias_base in &alias_bases {
-                aliases.push(alias_base.join(index_path));
+                aliases.push(alias_base.join(&relative_path));
             }
-        }
 
-        let front_matter = FrontMatter {
-            path: format!("{number}/assets/{}", relative_path.to_str().unwrap()),
-            aliases,
-            ..Default::default()
-        };
+            if relative_path.ends_with("README") || relative_path.ends_with("index") {
+                let index_path = relative_path.parent().unwrap();
+                for alias_base in &alias_bases {
+                    aliases.push(alias_base.join(index_path));
+                }
+            }
+
+            let front_matter = FrontMatter {
+                path: format!("{number}/assets/{}", relative_path.to_str().unwrap()),
+                aliases,
+                ..Default::default()
+            };
 
-        write_file(path, front_matter, &contents).whatever_context("couldn't write file")?;
+            Ok(render_file(front_matter, &contents))
+        })?;
     }
 
     Ok(())
 }
 
-fn process_eip(root: &Path, path: &Path) -> Result<(), Whatever> {
+fn process_eip(
+    root: &Path,
+    source: &Path,
+    path: &Path,
+    last_modified: &LastModified,
+    store: Option<&Store>,
+) -> Result<(), Error> {
+    let contents = read_to_string(source)
+        .with_whatever_context(|_| format!("could not read file `{}`", source.to_string_lossy()))?;
+
+    let updated = match path.file_name() {
+        Some(x) if x == "_index.md" => None,
+        _ => self::last_modified(root, path, last_modified)?,
+    };
+
+    cached(root, path, &contents, updated.as_ref(), store, |links| {
+        render_eip(root, path, &contents, updated, links)
+    })
+}
+
+fn render_eip(
+    root: &Path,
+    path: &Path,
+    contents: &str,
+    updated: Option<Datetime>,
+    links: &mut Vec<Link>,
+) -> Result<String, Error> {
     let path_lossy = path.to_string_lossy();
-    let contents = read_to_string(path)
-        .with_whatever_context(|_| format!("could not read file `{}`", path_lossy))?;
 
-    let (preamble, body) = Preamble::split(&contents)
+    let (preamble, body) = Preamble::split(contents)
         .with_whatever_context(|_| format!("couldn't split preamble for `{}`", path_lossy))?;
 
-    let body = transform_markdown(root, path, body)
+    let body = transform_markdown(root, path, body, links)
         .with_whatever_context(|_| format!("unable to transform markdown for `{path_lossy}`"))?;
 
     let preamble = Preamble::parse(Some(&path_lossy), preamble)
         .ok()
         .with_whatever_context(|| format!("couldn't parse preamble in `{}`", path_lossy))?;
 
-    let updated = match path.file_name() {
-        Some(x) if x == "_index.md" => None,
-        _ => Some(last_modified(path)?),
-    };
-
     let mut front_matter = FrontMatter {
         updated,
         ..Default::default()
@@ -655,7 +884,7 @@ fn process_eip(root: &Path, path: &Path) -> Result<(), Whatever> {
                     .into_iter()
                     .map(|eip| {
                         let path = format!("/{eip:0>5}.md");
-                        path_to_at(root, root, &path)
+                        resolve(root, root, &path, links)
                     })
                     .collect::<Result<_, _>>()?;
                 front_matter
@@ -669,7 +898,5 @@ fn process_eip(root: &Path, path: &Path) -> Result<(), Whatever> {
         }
     }
 
-    write_file(Path::new(&path), front_matter, &body).whatever_context("couldn't write file")?;
-
-    Ok(())
+    Ok(render_file(front_matter, &body))
 }
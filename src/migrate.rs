@@ -0,0 +1,497 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Converting proposals from the old Jekyll layout, for `build-eips migrate`.
+//!
+//! Proposals in `EIPS/eip-1234.md` (or `ERCS/erc-1234.md`) move to
+//! `content/01234.md`, or `content/01234/index.md` when they have assets in
+//! `assets/eip-1234/`. Links are rewritten for the new layout, and anchors
+//! for Zola's heading ids. See `MIGRATION.md`.
+
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    path::{Component, Path, PathBuf},
+};
+
+use eipw_preamble::Preamble;
+use iref::IriRefBuf;
+use log::{info, warn};
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use snafu::{ResultExt, Whatever};
+
+use crate::{layout::CONTENT_DIR, markdown};
+
+/// Directories proposals used to live in, with the prefix of their names.
+const OLD_DIRS: &[(&str, &str)] = &[("EIPS", "eip-"), ("ERCS", "erc-")];
+
+/// Directory assets used to live in, in subdirectories like `eip-1234`.
+const OLD_ASSETS_DIR: &str = "assets";
+
+/// Number of the proposal named `name` (like `eip-1234.md`), without the
+/// `.md` when `extension` is false.
+fn old_number(name: &str, extension: bool) -> Option<u64> {
+    let stem = match extension {
+        true => name.strip_suffix(".md")?,
+        false => name,
+    };
+    OLD_DIRS
+        .iter()
+        .find_map(|(_, prefix)| stem.strip_prefix(prefix))?
+        .parse()
+        .ok()
+}
+
+/// Where `old` (relative to the root) is after migrating, if it's a proposal
+/// or one of their assets.
+fn new_location(old: &Path) -> Option<PathBuf> {
+    let mut components = old.iter().map(|c| c.to_str());
+    let first = components.next()??;
+    let second = components.next()??;
+    let rest: PathBuf = old.iter().skip(2).collect();
+
+    if first == OLD_ASSETS_DIR {
+        let number = old_number(second, false)?;
+        let assets = Path::new(CONTENT_DIR).join(format!("{number:05}/assets"));
+        return Some(assets.join(rest));
+    }
+
+    if !rest.as_os_str().is_empty() || !OLD_DIRS.iter().any(|(d, _)| *d == first) {
+        return None;
+    }
+
+    let number = old_number(second, true)?;
+    Some(Path::new(CONTENT_DIR).join(format!("{number:05}.md")))
+}
+
+/// Resolve `link` from a file in `parent` to a path relative to the root,
+/// unless it leaves the root.
+fn resolve(parent: &Path, link: &str) -> Option<PathBuf> {
+    let joined = match link.strip_prefix('/') {
+        Some(absolute) => PathBuf::from(absolute),
+        None => parent.join(link),
+    };
+
+    let mut resolved = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::Normal(c) => resolved.push(c),
+            Component::CurDir => (),
+            Component::ParentDir if resolved.pop() => (),
+            _ => return None,
+        }
+    }
+    Some(resolved)
+}
+
+/// Link from a file in `from_dir` to `to` (both relative to the root.)
+pub(crate) fn relative(from_dir: &Path, to: &Path) -> String {
+    let from: Vec<_> = from_dir.iter().collect();
+    let to: Vec<_> = to.iter().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut parts = vec![OsStr::new(".."); from.len() - common];
+    if parts.is_empty() {
+        parts.push(OsStr::new("."));
+    }
+    parts.extend(&to[common..]);
+
+    let parts: Vec<_> = parts.iter().map(|p| p.to_string_lossy()).collect();
+    parts.join("/")
+}
+
+/// Heading id generated by Jekyll (with kramdown's GFM parser.)
+fn jekyll_id(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '_' || *c == '-' || *c == ' ')
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect()
+}
+
+/// Heading id generated by Zola, for headings that are plain ASCII (Zola
+/// transliterates anything else.)
+fn zola_id(text: &str) -> Option<String> {
+    if !text.is_ascii() {
+        return None;
+    }
+
+    let lower = text.to_ascii_lowercase();
+    let words: Vec<_> = lower
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+    Some(words.join("-"))
+}
+
+/// Heading ids in a proposal, as Jekyll generated them, mapped to the ids
+/// Zola generates.
+fn heading_ids(contents: &str) -> HashMap<String, Option<String>> {
+    let body = match Preamble::split(contents) {
+        Ok((_, body)) => body,
+        Err(_) => contents,
+    };
+
+    let mut anchors = HashMap::new();
+    let mut heading: Option<(Option<String>, String)> = None;
+
+    for event in Parser::new_ext(body, markdown::parser_options()) {
+        match (event, &mut heading) {
+            (Event::Start(Tag::Heading { id, .. }), _) => {
+                heading = Some((id.map(|i| i.into_string()), String::new()));
+            }
+            (Event::Text(t) | Event::Code(t), Some((_, text))) => text.push_str(&t),
+            (Event::End(TagEnd::Heading(_)), _) => match heading.take() {
+                Some((Some(id), _)) => {
+                    anchors.insert(id.clone(), Some(id));
+                }
+                Some((None, text)) => {
+                    anchors.insert(jekyll_id(&text), zola_id(&text));
+                }
+                None => (),
+            },
+            _ => (),
+        }
+    }
+
+    anchors
+}
+
+/// Destinations of `href` and `src` attributes in a proposal's raw HTML.
+fn html_links(contents: &str) -> Vec<String> {
+    let body = match Preamble::split(contents) {
+        Ok((_, body)) => body,
+        Err(_) => contents,
+    };
+
+    let mut links = Vec::new();
+    for event in Parser::new_ext(body, markdown::parser_options()) {
+        let (Event::Html(html) | Event::InlineHtml(html)) = event else {
+            continue;
+        };
+
+        for attribute in ["href=", "src="] {
+            for (start, _) in html.match_indices(attribute) {
+                if !html[..start].ends_with(char::is_whitespace) {
+                    continue;
+                }
+
+                let value = &html[start + attribute.len()..];
+                let Some(quote) = value.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+                    continue;
+                };
+                if let Some((link, _)) = value[1..].split_once(quote) {
+                    links.push(link.to_owned());
+                }
+            }
+        }
+    }
+
+    links
+}
+
+/// A proposal in the old layout.
+#[derive(Debug)]
+struct Proposal {
+    number: u64,
+
+    /// Where the proposal is now, relative to the root.
+    from: PathBuf,
+
+    /// Where its assets are now, if it has any.
+    assets: Option<PathBuf>,
+
+    /// Where the proposal is going.
+    to: PathBuf,
+
+    contents: String,
+}
+
+impl Proposal {
+    fn find(root_path: &Path) -> Result<Vec<Self>, Whatever> {
+        let mut proposals = Vec::new();
+
+        for (dir, _) in OLD_DIRS {
+            let entries = match std::fs::read_dir(root_path.join(dir)) {
+                Ok(e) => e,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e).whatever_context(format!("unable to read `{dir}`")),
+            };
+
+            for entry in entries {
+                let entry = entry.with_whatever_context(|_| format!("unable to read `{dir}`"))?;
+                let name = entry.file_name();
+                let Some(number) = name.to_str().and_then(|n| old_number(n, true)) else {
+                    continue;
+                };
+
+                let from = Path::new(dir).join(&name);
+                let contents = std::fs::read_to_string(root_path.join(&from))
+                    .with_whatever_context(|_| {
+                        format!("unable to read `{}`", from.to_string_lossy())
+                    })?;
+
+                let stem = Path::new(&name).with_extension("");
+                let assets = Path::new(OLD_ASSETS_DIR).join(stem);
+                let assets = root_path.join(&assets).is_dir().then_some(assets);
+
+                let to = match assets {
+                    Some(_) => Path::new(CONTENT_DIR).join(format!("{number:05}/index.md")),
+                    None => Path::new(CONTENT_DIR).join(format!("{number:05}.md")),
+                };
+
+                proposals.push(Proposal {
+                    number,
+                    from,
+                    assets,
+                    to,
+                    contents,
+                });
+            }
+        }
+
+        proposals.sort_by_key(|p| p.number);
+        Ok(proposals)
+    }
+
+    /// Anchor to use in the new layout for `fragment`, if it can be found in
+    /// `anchors`.
+    fn anchor(
+        &self,
+        fragment: &str,
+        anchors: Option<&HashMap<String, Option<String>>>,
+        problems: &mut Vec<String>,
+    ) -> String {
+        let Some(anchors) = anchors else {
+            return fragment.to_owned();
+        };
+
+        match anchors.get(fragment) {
+            Some(Some(id)) => id.clone(),
+            Some(None) => {
+                problems.push(format!("can't convert anchor `#{fragment}`"));
+                fragment.to_owned()
+            }
+            // Already matches Zola, or doesn't match anything at all.
+            None if anchors.values().any(|id| id.as_deref() == Some(fragment)) => {
+                fragment.to_owned()
+            }
+            None => {
+                problems.push(format!("anchor `#{fragment}` doesn't match a heading"));
+                fragment.to_owned()
+            }
+        }
+    }
+
+    /// Destination to use in the new layout for a link to `dest`.
+    fn link(
+        &self,
+        dest: &str,
+        anchors: &HashMap<u64, HashMap<String, Option<String>>>,
+        problems: &mut Vec<String>,
+    ) -> Option<String> {
+        let iri_ref = IriRefBuf::new(dest.to_owned()).ok()?;
+        if iri_ref.scheme().is_some() || iri_ref.authority().is_some() {
+            return None;
+        }
+
+        let path = iri_ref.path().as_str();
+        let fragment = iri_ref.fragment().map(|f| f.as_str());
+        let suffix = &dest[path.len()..];
+
+        if path.is_empty() {
+            let fragment = fragment?;
+            let anchor = self.anchor(fragment, anchors.get(&self.number), problems);
+            return (anchor != fragment).then(|| format!("#{anchor}"));
+        }
+
+        let Some(old) = resolve(self.from.parent().unwrap(), path) else {
+            problems.push(format!(
+                "can't convert link to `{dest}` (outside repository)"
+            ));
+            return None;
+        };
+
+        if old == Path::new("LICENSE.md") {
+            return Some(format!("/LICENSE.md{suffix}"));
+        }
+
+        let Some(new) = new_location(&old) else {
+            problems.push(format!("can't convert link to `{dest}`"));
+            return None;
+        };
+
+        let mut output = relative(self.to.parent().unwrap(), &new);
+        match fragment {
+            Some(fragment) => {
+                let number = old_number(&old.file_name()?.to_string_lossy(), true);
+                let anchors = number.and_then(|n| anchors.get(&n));
+                let anchor = self.anchor(fragment, anchors, problems);
+                output.push('#');
+                output.push_str(&anchor);
+            }
+            None => output.push_str(suffix),
+        }
+        Some(output)
+    }
+
+    /// Contents of the proposal in the new layout.
+    fn convert(
+        &self,
+        anchors: &HashMap<u64, HashMap<String, Option<String>>>,
+        problems: &mut Vec<String>,
+    ) -> String {
+        if self.contents.contains("{%") || self.contents.contains("{{") {
+            problems.push("uses Liquid templating, which Zola doesn't support".into());
+        }
+
+        // Only markdown links are rewritten, so say which HTML ones need it.
+        for dest in html_links(&self.contents) {
+            let mut unused = Vec::new();
+            if let Some(new) = self.link(&dest, anchors, &mut unused) {
+                problems.push(format!(
+                    "can't convert HTML link to `{dest}` (should be `{new}`)"
+                ));
+            } else if !unused.is_empty() {
+                problems.push(format!("can't convert HTML link to `{dest}`"));
+            }
+        }
+
+        let mut output = self.contents.clone();
+        let links = markdown::link_destinations(&self.contents);
+        for (range, dest) in links.into_iter().rev() {
+            if let Some(new) = self.link(&dest, anchors, problems) {
+                output.replace_range(range, &new);
+            }
+        }
+        output
+    }
+
+    fn migrate(&self, root_path: &Path, contents: String) -> Result<(), Whatever> {
+        let to = root_path.join(&self.to);
+        std::fs::create_dir_all(to.parent().unwrap())
+            .with_whatever_context(|_| format!("unable to create `{}`", to.to_string_lossy()))?;
+
+        if let Some(assets) = &self.assets {
+            let new = to.with_file_name("assets");
+            std::fs::rename(root_path.join(assets), &new).with_whatever_context(|_| {
+                format!("unable to move `{}`", assets.to_string_lossy())
+            })?;
+        }
+
+        std::fs::write(&to, contents)
+            .with_whatever_context(|_| format!("unable to write `{}`", to.to_string_lossy()))?;
+        std::fs::remove_file(root_path.join(&self.from)).with_whatever_context(|_| {
+            format!("unable to remove `{}`", self.from.to_string_lossy())
+        })
+    }
+}
+
+/// Move proposals in the old layout in the root checkout to the new one.
+pub(crate) fn run(root_path: &Path) -> Result<(), Whatever> {
+    let proposals = Proposal::find(root_path)?;
+    if proposals.is_empty() {
+        info!("no proposals to migrate");
+        return Ok(());
+    }
+
+    let anchors: HashMap<_, _> = proposals
+        .iter()
+        .map(|p| (p.number, heading_ids(&p.contents)))
+        .collect();
+
+    let mut n_problems = 0;
+    for proposal in &proposals {
+        let mut problems = Vec::new();
+
+        let number = format!("{:05}", proposal.number);
+        let content = root_path.join(CONTENT_DIR);
+        if content.join(format!("{number}.md")).exists() || content.join(&number).exists() {
+            problems.push(format!(
+                "not migrated, since proposal {number} already exists"
+            ));
+        } else {
+            let contents = proposal.convert(&anchors, &mut problems);
+            proposal.migrate(root_path, contents)?;
+            info!(
+                "migrated `{}` to `{}`",
+                proposal.from.to_string_lossy(),
+                proposal.to.to_string_lossy()
+            );
+        }
+
+        for problem in &problems {
+            warn!("`{}`: {problem}", proposal.from.to_string_lossy());
+        }
+        n_problems += problems.len();
+    }
+
+    if n_problems > 0 {
+        warn!("{n_problems} problem(s) need to be fixed by hand");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_proposal() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("EIPS")).unwrap();
+        std::fs::create_dir_all(root.join("assets/eip-20")).unwrap();
+        std::fs::write(root.join("assets/eip-20/x.png"), "").unwrap();
+
+        std::fs::write(
+            root.join("EIPS/eip-20.md"),
+            "---\neip: 20\n---\n\n\
+            ## Motivation / Goals\n\n\
+            See [goals](#motivation--goals), [one](./eip-1.md#spec), and \
+            [nothing](#nope).\n\n\
+            ![x](../assets/eip-20/x.png)\n\n\
+            Waived via [CC0](../LICENSE.md).\n",
+        )
+        .unwrap();
+
+        run(root).unwrap();
+
+        assert!(!root.join("EIPS/eip-20.md").exists());
+        assert!(root.join("content/00020/assets/x.png").is_file());
+        assert_eq!(
+            std::fs::read_to_string(root.join("content/00020/index.md")).unwrap(),
+            "---\neip: 20\n---\n\n\
+            ## Motivation / Goals\n\n\
+            See [goals](#motivation-goals), [one](../00001.md#spec), and \
+            [nothing](#nope).\n\n\
+            ![x](./assets/x.png)\n\n\
+            Waived via [CC0](/LICENSE.md).\n",
+        );
+    }
+
+    #[test]
+    fn reports_html_links() {
+        let proposal = Proposal {
+            number: 20,
+            from: PathBuf::from("EIPS/eip-20.md"),
+            assets: None,
+            to: PathBuf::from("content/00020.md"),
+            contents: "---\neip: 20\n---\n\n\
+                <a href=\"./eip-1.md\">one</a> <img src='https://example.test/x.png'>\n"
+                .into(),
+        };
+
+        let mut problems = Vec::new();
+        proposal.convert(&HashMap::new(), &mut problems);
+
+        assert_eq!(
+            problems,
+            ["can't convert HTML link to `./eip-1.md` (should be `./00001.md`)"]
+        );
+    }
+}
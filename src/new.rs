@@ -0,0 +1,303 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Scaffolding for new proposals, for `build-eips new`.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use eipw_preamble::Preamble;
+use log::{info, warn};
+use snafu::{whatever, OptionExt, ResultExt, Whatever};
+
+use crate::{changed, layout::CONTENT_DIR};
+
+/// Template for the body of new proposals, relative to the root.
+const TEMPLATE_FILE: &str = "docs/template.md";
+
+/// Body used when the repository doesn't have a template.
+const DEFAULT_BODY: &str = "\
+## Abstract
+
+## Motivation
+
+## Specification
+
+## Rationale
+
+## Backwards Compatibility
+
+## Security Considerations
+
+## Copyright
+
+Copyright and related rights waived via [CC0](/LICENSE.md).
+";
+
+#[derive(Debug, clap::Args, Clone)]
+#[group(id = "new")]
+pub struct CmdArgs {
+    /// Number for the proposal (instead of a placeholder, counting down from
+    /// 99999)
+    #[clap(long)]
+    number: Option<u64>,
+
+    /// Title of the proposal
+    #[clap(long)]
+    title: String,
+
+    /// One sentence describing the proposal
+    #[clap(long)]
+    description: String,
+
+    /// Author, like `Name (@github) <email>` (instead of `user.name` and
+    /// `user.email` from git)
+    #[clap(long)]
+    author: Option<String>,
+
+    /// Where the proposal is discussed
+    #[clap(long)]
+    discussions_to: Option<String>,
+
+    /// Type of the proposal
+    #[clap(long("type"), value_enum)]
+    kind: Kind,
+
+    /// Category of Standards Track proposals
+    #[clap(long, value_enum, required_if_eq("kind", "standards-track"))]
+    category: Option<Category>,
+
+    /// Create a directory with room for assets, instead of a single file
+    #[clap(long)]
+    assets: bool,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum Kind {
+    StandardsTrack,
+    Meta,
+    Informational,
+}
+
+impl Kind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::StandardsTrack => "Standards Track",
+            Self::Meta => "Meta",
+            Self::Informational => "Informational",
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum Category {
+    Core,
+    Networking,
+    Interface,
+    Erc,
+}
+
+impl Category {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Core => "Core",
+            Self::Networking => "Networking",
+            Self::Interface => "Interface",
+            Self::Erc => "ERC",
+        }
+    }
+}
+
+/// Placeholder numbers count down from here, so they're easy to tell apart
+/// from (and don't collide with) numbers assigned by editors.
+const PLACEHOLDER_MAX: u64 = 99_999;
+
+/// The highest number up to [`PLACEHOLDER_MAX`] that no proposal in
+/// `content_path` uses.
+fn placeholder(content_path: &Path) -> Result<u64, Whatever> {
+    let entries = std::fs::read_dir(content_path).with_whatever_context(|_| {
+        format!("unable to read `{}`", content_path.to_string_lossy())
+    })?;
+
+    let mut taken = HashSet::new();
+    for entry in entries {
+        let entry = entry.whatever_context("unable to read content directory")?;
+        let name = Path::new(entry.file_name().as_os_str()).with_extension("");
+        if let Some(n) = name.to_str().and_then(|n| n.parse::<u64>().ok()) {
+            taken.insert(n);
+        }
+    }
+
+    (1..=PLACEHOLDER_MAX)
+        .rev()
+        .find(|n| !taken.contains(n))
+        .whatever_context("no placeholder numbers left (use `--number`)")
+}
+
+/// `Name <email>` from the git configuration.
+fn git_author(root_path: &Path) -> Result<String, Whatever> {
+    let config = git2::Repository::open(root_path)
+        .and_then(|r| r.config())
+        .or_else(|_| git2::Config::open_default())
+        .whatever_context("unable to read git configuration")?;
+
+    let name = config
+        .get_string("user.name")
+        .ok()
+        .whatever_context("no author given, and `user.name` isn't set in git")?;
+
+    match config.get_string("user.email") {
+        Ok(email) => Ok(format!("{name} <{email}>")),
+        Err(_) => Ok(name),
+    }
+}
+
+/// Body of the repository's template, or a minimal one without it.
+fn body(root_path: &Path) -> String {
+    let path = root_path.join(TEMPLATE_FILE);
+    let template = match std::fs::read_to_string(&path) {
+        Ok(t) => t,
+        Err(e) => {
+            warn!(
+                "using a minimal template (unable to read `{}`: {e})",
+                path.to_string_lossy()
+            );
+            return DEFAULT_BODY.to_owned();
+        }
+    };
+
+    match Preamble::split(&template) {
+        Ok((_, body)) => body.trim_start().to_owned(),
+        Err(_) => template,
+    }
+}
+
+fn contents(number: u64, author: &str, body: &str, args: &CmdArgs) -> String {
+    let created = chrono::Local::now().date_naive().format("%Y-%m-%d");
+
+    let mut fields = vec![
+        format!("eip: {number}"),
+        format!("title: {}", args.title),
+        format!("description: {}", args.description),
+        format!("author: {author}"),
+    ];
+    if let Some(discussions_to) = &args.discussions_to {
+        fields.push(format!("discussions-to: {discussions_to}"));
+    }
+    fields.push("status: Draft".into());
+    fields.push(format!("type: {}", args.kind.as_str()));
+    if let Some(category) = args.category {
+        fields.push(format!("category: {}", category.as_str()));
+    }
+    fields.push(format!("created: {created}"));
+
+    format!("---\n{}\n---\n\n{body}", fields.join("\n"))
+}
+
+/// Create a new proposal in the root checkout, returning its path.
+pub fn create(root_path: &Path, args: &CmdArgs) -> Result<PathBuf, Whatever> {
+    let content_path = root_path.join(CONTENT_DIR);
+
+    let number = match args.number {
+        Some(n) => n,
+        None => {
+            let n = placeholder(&content_path)?;
+            info!(
+                "using {n} as a placeholder number (an editor will assign the real one, \
+                then use `build-eips renumber`)"
+            );
+            n
+        }
+    };
+
+    let name = format!("{number:05}");
+    for existing in [format!("{name}.md"), name.clone()] {
+        if content_path.join(&existing).exists() {
+            whatever!("`{CONTENT_DIR}/{existing}` already exists");
+        }
+    }
+
+    let relative = if args.assets {
+        Path::new(CONTENT_DIR).join(&name).join("index.md")
+    } else {
+        Path::new(CONTENT_DIR).join(format!("{name}.md"))
+    };
+    debug_assert!(changed::is_proposal_path(relative.clone()));
+
+    let author = match &args.author {
+        Some(a) => a.clone(),
+        None => git_author(root_path)?,
+    };
+
+    let path = root_path.join(&relative);
+    if args.assets {
+        let assets = content_path.join(&name).join("assets");
+        std::fs::create_dir_all(&assets).with_whatever_context(|_| {
+            format!("unable to create `{}`", assets.to_string_lossy())
+        })?;
+    }
+
+    std::fs::write(&path, contents(number, &author, &body(root_path), args))
+        .with_whatever_context(|_| format!("unable to write `{}`", path.to_string_lossy()))?;
+
+    info!("created `{}`", relative.to_string_lossy());
+    Ok(relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Args {
+        #[command(flatten)]
+        new: CmdArgs,
+    }
+
+    #[test]
+    fn creates_with_placeholder_number() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = dir.path().join(CONTENT_DIR);
+        std::fs::create_dir_all(content.join("99999")).unwrap();
+        std::fs::write(content.join("00003.md"), "").unwrap();
+        std::fs::write(content.join("_index.md"), "").unwrap();
+
+        let args = Args::parse_from([
+            "new",
+            "--title=Example",
+            "--description=An example",
+            "--author=Alice (@alice)",
+            "--type=meta",
+            "--assets",
+        ]);
+
+        let created = create(dir.path(), &args.new).unwrap();
+        assert_eq!(created, Path::new("content/99998/index.md"));
+        assert!(content.join("99998/assets").is_dir());
+
+        let contents = std::fs::read_to_string(dir.path().join(created)).unwrap();
+        assert!(contents.starts_with("---\neip: 99998\ntitle: Example\n"));
+        assert!(contents.contains("\ntype: Meta\n"));
+        assert!(contents.ends_with(DEFAULT_BODY));
+
+        assert!(create(
+            dir.path(),
+            &Args::parse_from([
+                "new",
+                "--number=3",
+                "--title=T",
+                "--description=D",
+                "--author=A",
+                "--type=meta"
+            ])
+            .new
+        )
+        .is_err());
+    }
+}
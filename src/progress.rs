@@ -7,10 +7,12 @@
 use std::iter::FusedIterator;
 
 use indicatif::{
-    MultiProgress, ProgressBar, ProgressBarIter, ProgressFinish, ProgressIterator, ProgressStyle,
+    MultiProgress, ParallelProgressIterator, ProgressBar, ProgressBarIter, ProgressFinish,
+    ProgressIterator, ProgressStyle,
 };
 use indicatif_log_bridge::LogWrapper;
 use log::Log;
+use rayon::iter::{plumbing::UnindexedConsumer, IndexedParallelIterator, ParallelIterator};
 
 lazy_static::lazy_static! {
     static ref PROGRESS: MultiProgress = MultiProgress::new();
@@ -118,6 +120,58 @@ impl<T> Drop for ProgressBarExtIter<T> {
     }
 }
 
+pub trait ParallelProgressIteratorExt: IndexedParallelIterator {
+    fn progress_ext(self, prefix: &'static str) -> ParProgressBarExtIter<Self>;
+}
+
+impl<T> ParallelProgressIteratorExt for T
+where
+    T: IndexedParallelIterator,
+{
+    fn progress_ext(self, prefix: &'static str) -> ParProgressBarExtIter<Self> {
+        let len = self.len();
+        if len == 0 {
+            return ParProgressBarExtIter::Without(self);
+        }
+
+        let bar = ProgressBar::new(len as _);
+        PROGRESS.add(bar.clone());
+        let it = self
+            .progress_with(bar.clone())
+            .with_style(STYLE.clone())
+            .with_prefix(prefix)
+            .with_finish(ProgressFinish::AndLeave);
+
+        ParProgressBarExtIter::With { bar, it }
+    }
+}
+
+pub enum ParProgressBarExtIter<T> {
+    With {
+        bar: ProgressBar,
+        it: ProgressBarIter<T>,
+    },
+    Without(T),
+}
+
+impl<S: Send, T: ParallelIterator<Item = S>> ParallelIterator for ParProgressBarExtIter<T> {
+    type Item = S;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        let (it, bar) = match self {
+            Self::Without(it) => return it.drive_unindexed(consumer),
+            Self::With { it, bar } => (it, bar),
+        };
+
+        let result = it.drive_unindexed(consumer);
+        PROGRESS.remove(&bar);
+        result
+    }
+}
+
 pub struct Git(Option<ProgressBar>);
 
 impl Git {
@@ -0,0 +1,134 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! [Reviewdog diagnostic format](https://github.com/reviewdog/reviewdog/tree/master/proto/rdf)
+//! (rdjson) output, for review bots on forges other than GitHub.
+
+use std::{cell::RefCell, ops::Range};
+
+use eipw_lint::reporters;
+use eipw_snippets::{Level, Message, Snippet};
+use serde_json::{json, Value};
+
+use crate::{
+    lint::help_url,
+    span::{self, Position},
+};
+
+#[derive(Debug)]
+pub struct Reporter {
+    root: String,
+    diagnostics: RefCell<Vec<Value>>,
+}
+
+fn severity(level: Level) -> &'static str {
+    match level {
+        Level::Error => "ERROR",
+        Level::Warning => "WARNING",
+        Level::Info | Level::Note | Level::Help => "INFO",
+    }
+}
+
+// Reviewdog counts columns in bytes.
+fn position(position: Position) -> Value {
+    json!({ "line": position.line, "column": position.byte_column })
+}
+
+impl Reporter {
+    pub fn new(root: String) -> Self {
+        Self {
+            root,
+            diagnostics: Default::default(),
+        }
+    }
+
+    fn location(&self, snippet: &Snippet<'_>, range: Range<usize>) -> Option<Value> {
+        let origin = snippet.origin.as_deref()?;
+        let path = origin.strip_prefix(&self.root).unwrap_or(origin);
+        let path = path.strip_prefix('/').unwrap_or(path);
+
+        let span = span::span(snippet, &range);
+        Some(json!({
+            "path": path,
+            "range": {
+                "start": position(span.start),
+                "end": position(span.end),
+            },
+        }))
+    }
+
+    /// The complete diagnostic result for everything reported so far.
+    pub fn result(&self) -> Value {
+        json!({
+            "source": {
+                "name": env!("CARGO_PKG_NAME"),
+                "url": env!("CARGO_PKG_REPOSITORY"),
+            },
+            "diagnostics": *self.diagnostics.borrow(),
+        })
+    }
+}
+
+impl reporters::Reporter for Reporter {
+    fn report(&self, msg: Message<'_>) -> Result<(), reporters::Error> {
+        let mut text = msg.title.to_string();
+        for footer in &msg.footer {
+            text.push('\n');
+            text.push_str(&footer.title);
+        }
+
+        let mut diagnostic = json!({
+            "message": text,
+            "severity": severity(msg.level),
+        });
+
+        if let Some(id) = msg.id.as_deref() {
+            diagnostic["code"] = json!({ "value": id, "url": help_url(id) });
+        }
+
+        // Reviewdog only takes one location per diagnostic, so each snippet
+        // gets its own: the first annotation (or the whole snippet) is the
+        // location, and any other labelled annotations are related.
+        let mut diagnostics = Vec::new();
+
+        for snippet in &msg.snippets {
+            let mut annotations = snippet.annotations.iter();
+            let first = annotations.next();
+            let primary = match first {
+                Some(a) => a.range.clone(),
+                None => span::whole(snippet),
+            };
+
+            let location = match self.location(snippet, primary) {
+                Some(l) => l,
+                None => continue,
+            };
+
+            let related: Vec<_> = annotations
+                .filter_map(|a| {
+                    let label = a.label.as_deref()?;
+                    let location = self.location(snippet, a.range.clone())?;
+                    Some(json!({ "message": label, "location": location }))
+                })
+                .collect();
+
+            let mut diagnostic = diagnostic.clone();
+            diagnostic["location"] = location;
+            if !related.is_empty() {
+                diagnostic["related_locations"] = related.into();
+            }
+            diagnostics.push(diagnostic);
+        }
+
+        if diagnostics.is_empty() {
+            diagnostics.push(diagnostic);
+        }
+
+        self.diagnostics.borrow_mut().extend(diagnostics);
+
+        Ok(())
+    }
+}
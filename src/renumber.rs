@@ -0,0 +1,292 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Moving a proposal to a new number, for `build-eips renumber`.
+//!
+//! Links are resolved the same way as when preprocessing, so every link that
+//! would have led to the old proposal leads to the new one afterwards.
+
+use std::{
+    ffi::OsStr,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use eipw_preamble::Preamble;
+use iref::IriRefBuf;
+use log::{info, warn};
+use snafu::{whatever, ResultExt, Whatever};
+use walkdir::WalkDir;
+
+use crate::{
+    config::{self, Manifest},
+    layout::CONTENT_DIR,
+    markdown,
+};
+
+/// Replace `range` of a file with `text`.
+type Edit = (Range<usize>, String);
+
+/// Offset of `part` in `whole`, which it must be a subslice of.
+fn offset(whole: &str, part: &str) -> usize {
+    part.as_ptr() as usize - whole.as_ptr() as usize
+}
+
+/// The proposal numbered `number` in `content_path`, if it exists.
+fn locate(content_path: &Path, number: u64) -> Option<PathBuf> {
+    let name = format!("{number:05}");
+    [
+        content_path.join(format!("{name}.md")),
+        content_path.join(name),
+    ]
+    .into_iter()
+    .find(|p| p.exists())
+}
+
+struct Renumber<'a> {
+    /// Absolute links are relative to the content directory, like when
+    /// preprocessing.
+    content_path: &'a Path,
+    old: u64,
+    new: u64,
+
+    /// Where links to the old proposal resolve to, like `@/00001.md`.
+    target: String,
+}
+
+impl Renumber<'_> {
+    /// `path` from a link, with the old proposal's name replaced by the new
+    /// one's.
+    fn rename(&self, path: &str) -> String {
+        let old = format!("{:05}", self.old);
+        let new = format!("{:05}", self.new);
+        path.split('/')
+            .map(|segment| match segment.strip_suffix(".md") {
+                Some(stem) if stem == old => format!("{new}.md"),
+                _ if segment == old => new.clone(),
+                _ => segment.to_owned(),
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Edit for a link to `dest` at `range` in the file at `path`, if it points
+    /// at the old proposal.
+    fn link(&self, path: &Path, range: Range<usize>, dest: &str) -> Option<Edit> {
+        let iri_ref = IriRefBuf::new(dest.to_owned()).ok()?;
+        if iri_ref.authority().is_some() || !iri_ref.path().ends_with(".md") {
+            return None;
+        }
+
+        let link = iri_ref.path().as_str();
+        match markdown::path_to_at(self.content_path, path.parent().unwrap(), link) {
+            Ok(at) if at == self.target => (),
+            Ok(_) => return None,
+            Err(e) => {
+                warn!(
+                    "not updating link to `{link}` in `{}`: {e}",
+                    path.to_string_lossy()
+                );
+                return None;
+            }
+        }
+
+        let renamed = format!("{}{}", self.rename(link), &dest[link.len()..]);
+        Some((range, renamed))
+    }
+
+    fn preamble(&self, path: &Path, contents: &str, edits: &mut Vec<Edit>) {
+        let Ok((preamble, _)) = Preamble::split(contents) else {
+            return;
+        };
+        let Ok(preamble) = Preamble::parse(None, preamble) else {
+            warn!("not updating preamble of `{}`", path.to_string_lossy());
+            return;
+        };
+
+        for field in preamble.fields() {
+            let value = field.value().trim();
+            let replacement = match field.name() {
+                "eip" if value.parse() == Ok(self.old) => format!("eip: {}", self.new),
+                "requires" => {
+                    let Ok(mut numbers) = value
+                        .split(',')
+                        .map(|n| n.trim().parse::<u64>())
+                        .collect::<Result<Vec<_>, _>>()
+                    else {
+                        continue;
+                    };
+                    if !numbers.contains(&self.old) {
+                        continue;
+                    }
+                    for n in &mut numbers {
+                        if *n == self.old {
+                            *n = self.new;
+                        }
+                    }
+                    numbers.sort_unstable();
+                    numbers.dedup();
+                    let numbers: Vec<_> = numbers.iter().map(u64::to_string).collect();
+                    format!("requires: {}", numbers.join(", "))
+                }
+                _ => continue,
+            };
+
+            let start = offset(contents, field.source());
+            edits.push((start..start + field.source().len(), replacement));
+        }
+    }
+
+    fn links(&self, path: &Path, contents: &str, edits: &mut Vec<Edit>) {
+        for (range, dest) in markdown::link_destinations(contents) {
+            edits.extend(self.link(path, range, &dest));
+        }
+    }
+
+    /// Edits to `path`, which is some markdown file in the content directory.
+    fn edits(&self, path: &Path) -> Result<Option<String>, Whatever> {
+        let contents = std::fs::read_to_string(path)
+            .with_whatever_context(|_| format!("unable to read `{}`", path.to_string_lossy()))?;
+
+        let mut edits = Vec::new();
+        self.preamble(path, &contents, &mut edits);
+        self.links(path, &contents, &mut edits);
+
+        if edits.is_empty() {
+            return Ok(None);
+        }
+
+        edits.sort_by_key(|(range, _)| range.start);
+        edits.dedup_by_key(|(range, _)| range.start);
+
+        let mut output = contents.clone();
+        for (range, text) in edits.into_iter().rev() {
+            output.replace_range(range, &text);
+        }
+
+        Ok(Some(output))
+    }
+}
+
+/// Move proposal `old` to `new` in the root checkout, and update `requires`
+/// and links that refer to it.
+pub(crate) fn run(root_path: &Path, old: u64, new: u64) -> Result<(), Whatever> {
+    let content_path = root_path.join(CONTENT_DIR);
+
+    let Some(from) = locate(&content_path, old) else {
+        whatever!("there is no proposal numbered {old}");
+    };
+    if let Some(existing) = locate(&content_path, new) {
+        whatever!("`{}` already exists", existing.to_string_lossy());
+    }
+
+    let target = markdown::path_to_at(&content_path, &content_path, &format!("/{old:05}.md"))
+        .whatever_context("unable to resolve proposal")?;
+    let renumber = Renumber {
+        content_path: &content_path,
+        old,
+        new,
+        target,
+    };
+
+    let files = WalkDir::new(&content_path).into_iter().filter(|e| match e {
+        Ok(f) => f.file_type().is_file() && f.path().extension() == Some(OsStr::new("md")),
+        Err(_) => true,
+    });
+
+    // Find every edit before changing anything, so links resolve against the
+    // tree as it was.
+    let mut edited = Vec::new();
+    for file in files {
+        let file = file.whatever_context("unable to read content directory")?;
+        if let Some(contents) = renumber.edits(file.path())? {
+            edited.push((file.into_path(), contents));
+        }
+    }
+
+    for (path, contents) in &edited {
+        std::fs::write(path, contents)
+            .with_whatever_context(|_| format!("unable to write `{}`", path.to_string_lossy()))?;
+    }
+
+    let to = match from.extension() {
+        Some(_) => content_path.join(format!("{new:05}.md")),
+        None => content_path.join(format!("{new:05}")),
+    };
+    std::fs::rename(&from, &to).with_whatever_context(|_| {
+        format!(
+            "unable to move `{}` to `{}`",
+            from.to_string_lossy(),
+            to.to_string_lossy()
+        )
+    })?;
+
+    if let Ok(manifest) = Manifest::load(root_path.join(config::MANIFEST_FILE)) {
+        if manifest.lint_overrides.contains_key(&old.to_string()) {
+            warn!(
+                "lint overrides for {old} in `{}` need to be moved by hand",
+                config::MANIFEST_FILE
+            );
+        }
+    }
+
+    info!(
+        "moved `{}` to `{}`, and updated {} file(s)",
+        from.strip_prefix(root_path)
+            .unwrap_or(&from)
+            .to_string_lossy(),
+        to.strip_prefix(root_path).unwrap_or(&to).to_string_lossy(),
+        edited.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renumbers_references() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = dir.path().join(CONTENT_DIR);
+        std::fs::create_dir_all(content.join("00002/assets")).unwrap();
+        std::fs::write(dir.path().join("outside.md"), "").unwrap();
+
+        std::fs::write(
+            content.join("00001.md"),
+            "---\neip: 1\nrequires: 2, 30\n---\n\n\
+            See [two](./00002/index.md#spec), [ref], and [one](./00001.md).\n\n\
+            [ref]: /00002.md\n\n\
+            [out]: ../outside.md\n",
+        )
+        .unwrap();
+        std::fs::write(
+            content.join("00002/index.md"),
+            "---\neip: 2\n---\n\n![x](./assets/x.png) [self](../00002.md)\n",
+        )
+        .unwrap();
+
+        run(dir.path(), 2, 40).unwrap();
+
+        assert!(!content.join("00002").exists());
+        assert!(content.join("00040/assets").is_dir());
+
+        assert_eq!(
+            std::fs::read_to_string(content.join("00001.md")).unwrap(),
+            "---\neip: 1\nrequires: 30, 40\n---\n\n\
+            See [two](./00040/index.md#spec), [ref], and [one](./00001.md).\n\n\
+            [ref]: /00040.md\n\n\
+            [out]: ../outside.md\n",
+        );
+        assert_eq!(
+            std::fs::read_to_string(content.join("00040/index.md")).unwrap(),
+            "---\neip: 40\n---\n\n![x](./assets/x.png) [self](../00040.md)\n",
+        );
+
+        assert!(run(dir.path(), 2, 41).is_err());
+        assert!(run(dir.path(), 1, 40).is_err());
+    }
+}
@@ -0,0 +1,201 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! A machine-readable summary of each build, written to `build/report.json`.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use serde::Serialize;
+use snafu::{Backtrace, ResultExt, Snafu};
+
+use crate::{
+    config::Theme,
+    git::{Merged, SourceWithUpstream},
+    layout::REPORT_FILE,
+    zola::Diagnostic,
+};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("i/o error accessing `{}`", path.to_string_lossy()))]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("unable to serialize `{}`", path.to_string_lossy()))]
+    Serialize {
+        path: PathBuf,
+        source: serde_json::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(transparent)]
+    Git {
+        #[snafu(backtrace)]
+        source: crate::git::Error,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Outcome {
+    Succeeded,
+    GitFailed,
+    LintFailed,
+    PreprocessFailed,
+    ZolaFailed,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Sibling {
+    pub name: String,
+    pub repository: String,
+    pub commit: String,
+}
+
+impl From<Merged> for Sibling {
+    fn from(merged: Merged) -> Self {
+        Self {
+            name: merged.name,
+            repository: merged.repository.into(),
+            commit: merged.commit.to_string(),
+        }
+    }
+}
+
+/// Number of lint messages at each level.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct Counts {
+    pub error: usize,
+    pub warning: usize,
+    pub info: usize,
+    pub note: usize,
+    pub help: usize,
+}
+
+impl From<eipw_lint::reporters::count::Counts> for Counts {
+    fn from(counts: eipw_lint::reporters::count::Counts) -> Self {
+        Self {
+            error: counts.error,
+            warning: counts.warning,
+            info: counts.info,
+            note: counts.note,
+            help: counts.help,
+        }
+    }
+}
+
+/// What linting found.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Lint {
+    /// Files that were linted, relative to the build repository.
+    pub files: Vec<PathBuf>,
+    pub counts: Counts,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BuildReport {
+    pub outcome: Outcome,
+
+    /// Commits being built, once they've been fetched.
+    pub source_head: Option<String>,
+    pub upstream_head: Option<String>,
+    pub merge_base: Option<String>,
+
+    pub siblings: Vec<Sibling>,
+    pub theme: Theme,
+    pub lint: Lint,
+    pub zola: Vec<Diagnostic>,
+
+    /// Seconds spent in each step of the build.
+    pub timings: BTreeMap<&'static str, f64>,
+
+    #[serde(skip)]
+    step_start: Instant,
+}
+
+impl BuildReport {
+    /// Start a report before the build does anything.
+    pub fn new(theme: Theme) -> Self {
+        Self {
+            outcome: Outcome::Succeeded,
+            source_head: None,
+            upstream_head: None,
+            merge_base: None,
+            siblings: Vec::new(),
+            theme,
+            lint: Lint::default(),
+            zola: Vec::new(),
+            timings: BTreeMap::new(),
+            step_start: Instant::now(),
+        }
+    }
+
+    /// Record the commits being built, once upstream has been fetched.
+    pub fn fetched(&mut self, repo: &SourceWithUpstream) -> Result<(), Error> {
+        self.source_head = Some(repo.local_head().to_string());
+        self.upstream_head = Some(repo.upstream_head().to_string());
+        self.merge_base = Some(repo.merge_base()?.to_string());
+        Ok(())
+    }
+
+    pub fn merged(&mut self, merged: Vec<Merged>) {
+        self.siblings = merged.into_iter().map(Sibling::from).collect();
+    }
+
+    /// Record the time since the previous step as spent in `name`.
+    pub fn step(&mut self, name: &'static str) {
+        let now = Instant::now();
+        *self.timings.entry(name).or_default() += (now - self.step_start).as_secs_f64();
+        self.step_start = now;
+    }
+
+    pub fn write(&self, build_path: &Path) -> Result<(), Error> {
+        let path = build_path.join(REPORT_FILE);
+        let json = serde_json::to_vec_pretty(self).context(SerializeSnafu { path: &path })?;
+        std::fs::write(&path, json).context(IoSnafu { path })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_outcome() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut report = BuildReport::new(Theme {
+            repository: "https://example.com/theme.git".parse().unwrap(),
+            commit: "d".into(),
+        });
+        report.step("lint");
+
+        for (outcome, expected) in [
+            (Outcome::Succeeded, "succeeded"),
+            (Outcome::GitFailed, "git-failed"),
+            (Outcome::LintFailed, "lint-failed"),
+            (Outcome::PreprocessFailed, "preprocess-failed"),
+            (Outcome::ZolaFailed, "zola-failed"),
+        ] {
+            report.outcome = outcome;
+            report.write(dir.path()).unwrap();
+
+            let written = std::fs::read(dir.path().join(REPORT_FILE)).unwrap();
+            let written: serde_json::Value = serde_json::from_slice(&written).unwrap();
+            assert_eq!(written["outcome"], expected);
+            assert_eq!(written["theme"]["commit"], "d");
+            assert!(written["source-head"].is_null());
+            assert!(written["timings"]["lint"].is_number());
+            assert!(written.get("step-start").is_none());
+        }
+    }
+}
@@ -0,0 +1,169 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! [SARIF 2.1.0](https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html)
+//! output, for code scanning tools.
+
+use std::{cell::RefCell, collections::BTreeSet, ops::Range};
+
+use eipw_lint::reporters;
+use eipw_snippets::{Level, Message, Snippet};
+use serde_json::{json, Value};
+
+use crate::{lint::help_url, span};
+
+/// Rule id for problems that don't come from a lint.
+const FALLBACK_RULE: &str = "build-eips";
+
+#[derive(Debug)]
+pub struct Reporter {
+    root: String,
+    rules: RefCell<BTreeSet<String>>,
+    results: RefCell<Vec<Value>>,
+}
+
+fn level(level: Level) -> &'static str {
+    match level {
+        Level::Error => "error",
+        Level::Warning => "warning",
+        Level::Info | Level::Note | Level::Help => "note",
+    }
+}
+
+fn region(snippet: &Snippet<'_>, range: Range<usize>) -> Value {
+    let span = span::span(snippet, &range);
+    let mut region = json!({
+        "startLine": span.start.line,
+        "startColumn": span.start.column,
+        "endLine": span.end.line,
+        "endColumn": span.end.column,
+    });
+    if let Some(text) = snippet.source.get(range) {
+        region["snippet"] = json!({ "text": text });
+    }
+    region
+}
+
+impl Reporter {
+    pub fn new(root: String) -> Self {
+        Self {
+            root,
+            rules: Default::default(),
+            results: Default::default(),
+        }
+    }
+
+    fn uri<'a>(&self, origin: &'a str) -> &'a str {
+        let stripped = origin.strip_prefix(&self.root).unwrap_or(origin);
+        stripped.strip_prefix('/').unwrap_or(stripped)
+    }
+
+    fn location(&self, snippet: &Snippet<'_>, range: Range<usize>) -> Option<Value> {
+        let origin = snippet.origin.as_deref()?;
+        Some(json!({
+            "physicalLocation": {
+                "artifactLocation": {
+                    "uri": self.uri(origin).replace('\\', "/"),
+                    "uriBaseId": "%SRCROOT%",
+                },
+                "region": region(snippet, range),
+            },
+        }))
+    }
+
+    /// The complete SARIF log for everything reported so far.
+    pub fn log(&self) -> Value {
+        let rules: Vec<_> = self
+            .rules
+            .borrow()
+            .iter()
+            .map(|id| json!({ "id": id, "helpUri": help_url(id) }))
+            .collect();
+
+        json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                        "rules": rules,
+                    },
+                },
+                "columnKind": "unicodeCodePoints",
+                "results": *self.results.borrow(),
+            }],
+        })
+    }
+}
+
+impl reporters::Reporter for Reporter {
+    fn report(&self, msg: Message<'_>) -> Result<(), reporters::Error> {
+        let rule_id = match msg.id.as_deref() {
+            Some(id) => {
+                self.rules.borrow_mut().insert(id.to_owned());
+                id
+            }
+            None => FALLBACK_RULE,
+        };
+
+        let mut text = msg.title.to_string();
+        for footer in &msg.footer {
+            text.push('\n');
+            text.push_str(&footer.title);
+        }
+
+        // The first annotation in each snippet (or the whole snippet, if it
+        // has none) is where the problem is, and any other labelled
+        // annotations give more detail.
+        let mut locations = Vec::new();
+        let mut related = Vec::new();
+
+        for snippet in &msg.snippets {
+            let mut annotations = snippet.annotations.iter();
+            let first = annotations.next();
+            let primary = match first {
+                Some(a) => a.range.clone(),
+                None => span::whole(snippet),
+            };
+            if let Some(mut location) = self.location(snippet, primary) {
+                if let Some(label) = first.and_then(|a| a.label.as_deref()) {
+                    location["message"] = json!({ "text": label });
+                }
+                locations.push(location);
+            }
+
+            for annotation in annotations {
+                let label = match &annotation.label {
+                    Some(l) => l,
+                    None => continue,
+                };
+                if let Some(mut location) = self.location(snippet, annotation.range.clone()) {
+                    location["id"] = related.len().into();
+                    location["message"] = json!({ "text": label });
+                    related.push(location);
+                }
+            }
+        }
+
+        let mut result = json!({
+            "ruleId": rule_id,
+            "level": level(msg.level),
+            "message": { "text": text },
+            "locations": locations,
+        });
+
+        if !related.is_empty() {
+            result["relatedLocations"] = related.into();
+        }
+
+        self.results.borrow_mut().push(result);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Converting byte ranges within snippets into lines and columns.
+
+use std::ops::Range;
+
+use eipw_snippets::Snippet;
+
+/// Location of a character in a file. All fields count from one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    /// Column, counted in characters.
+    pub column: usize,
+    /// Column, counted in bytes of UTF-8.
+    pub byte_column: usize,
+}
+
+/// Position of the character at byte `offset` into the snippet's source.
+pub fn position(snippet: &Snippet<'_>, offset: usize) -> Position {
+    let source = &snippet.source;
+
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+
+    let before = &source[..offset];
+    let line_offset = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+
+    Position {
+        line: snippet.line_start + before.matches('\n').count(),
+        column: before[line_offset..].chars().count() + 1,
+        byte_column: before.len() - line_offset + 1,
+    }
+}
+
+/// Start (inclusive) and end (exclusive) of `range` in the snippet's source.
+pub fn span(snippet: &Snippet<'_>, range: &Range<usize>) -> Range<Position> {
+    position(snippet, range.start)..position(snippet, range.end)
+}
+
+/// Byte range of the whole snippet, not counting any trailing newline.
+pub fn whole(snippet: &Snippet<'_>) -> Range<usize> {
+    0..snippet.source.trim_end_matches(['\r', '\n']).len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(line: usize, column: usize) -> Position {
+        Position {
+            line,
+            column,
+            byte_column: column,
+        }
+    }
+
+    #[test]
+    fn offsets_across_lines() {
+        let snippet = Snippet::source("eip: 1\ntitle: Foo\n").line_start(2);
+
+        assert_eq!(position(&snippet, 0), pos(2, 1));
+        assert_eq!(span(&snippet, &(7..12)), pos(3, 1)..pos(3, 6));
+        assert_eq!(span(&snippet, &(5..9)), pos(2, 6)..pos(3, 3));
+        assert_eq!(span(&snippet, &whole(&snippet)), pos(2, 1)..pos(3, 11));
+    }
+
+    #[test]
+    fn columns_in_characters_and_bytes() {
+        let snippet = Snippet::source("author: Zoë (@zoe)");
+
+        let span = span(&snippet, &(13..19));
+        assert_eq!((span.start.column, span.start.byte_column), (13, 14));
+        assert_eq!((span.end.column, span.end.byte_column), (19, 20));
+
+        // Offsets inside a character snap back to its start.
+        assert_eq!(position(&snippet, 11).byte_column, 11);
+    }
+}
@@ -0,0 +1,160 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Measuring how long linting takes, for `--timings`.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    io::Write,
+    path::PathBuf,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use clap::ValueEnum;
+use eipw_lint::lints::{Context, DefaultLint, Error, FetchContext, Lint};
+use serde::{Deserialize, Serialize};
+
+#[derive(Default, ValueEnum, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Format {
+    #[default]
+    Table,
+    Json,
+}
+
+/// Time spent in each lint, shared by all the lints of one linter.
+#[derive(Debug, Default, Clone)]
+pub struct Spent(Rc<RefCell<HashMap<String, Duration>>>);
+
+impl Spent {
+    pub fn take(&self) -> HashMap<String, Duration> {
+        self.0.take()
+    }
+}
+
+/// Wraps a lint to record how long it takes, including finding the resources
+/// (like other proposals) it needs.
+#[derive(Debug)]
+pub struct Timed<L> {
+    slug: String,
+    lint: L,
+    spent: Spent,
+}
+
+impl<L> Timed<L> {
+    pub fn new(slug: &str, lint: L, spent: &Spent) -> Self {
+        Self {
+            slug: slug.to_owned(),
+            lint,
+            spent: spent.clone(),
+        }
+    }
+
+    fn record(&self, slug: &str, start: Instant) {
+        *self
+            .spent
+            .0
+            .borrow_mut()
+            .entry(slug.to_owned())
+            .or_default() += start.elapsed();
+    }
+}
+
+// Lets `Options::default()` build options with timed lints, which can then be
+// replaced with the configured lints.
+impl<S> From<DefaultLint<&'static str>> for Timed<DefaultLint<S>>
+where
+    DefaultLint<&'static str>: Into<DefaultLint<S>>,
+{
+    fn from(lint: DefaultLint<&'static str>) -> Self {
+        Self {
+            slug: String::new(),
+            lint: lint.into(),
+            spent: Spent::default(),
+        }
+    }
+}
+
+impl<L: Lint> Lint for Timed<L> {
+    fn find_resources(&self, ctx: &FetchContext<'_>) -> Result<(), Error> {
+        let start = Instant::now();
+        let result = self.lint.find_resources(ctx);
+        self.record(&self.slug, start);
+        result
+    }
+
+    fn lint<'a>(&self, slug: &'a str, ctx: &Context<'a, '_>) -> Result<(), Error> {
+        let start = Instant::now();
+        let result = self.lint.lint(slug, ctx);
+        self.record(slug, start);
+        result
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct File {
+    path: PathBuf,
+    seconds: f64,
+    lints: BTreeMap<String, f64>,
+}
+
+/// How long each proposal (and each lint) took to lint.
+#[derive(Debug, Default, Serialize)]
+pub struct Timings {
+    files: Vec<File>,
+    lints: BTreeMap<String, f64>,
+}
+
+impl Timings {
+    pub fn push(&mut self, path: PathBuf, elapsed: Duration, lints: HashMap<String, Duration>) {
+        let lints: BTreeMap<_, _> = lints
+            .into_iter()
+            .map(|(slug, spent)| (slug, spent.as_secs_f64()))
+            .collect();
+
+        for (slug, seconds) in &lints {
+            *self.lints.entry(slug.clone()).or_default() += seconds;
+        }
+
+        self.files.push(File {
+            path,
+            seconds: elapsed.as_secs_f64(),
+            lints,
+        });
+    }
+
+    /// Write the timings to stderr, so they don't mix with lint output.
+    pub fn print(&self, format: Format) {
+        let mut stderr = std::io::stderr().lock();
+        match format {
+            Format::Json => {
+                serde_json::to_writer_pretty(&mut stderr, self).unwrap();
+                writeln!(stderr).unwrap();
+            }
+            Format::Table => {
+                let mut files: Vec<_> = self
+                    .files
+                    .iter()
+                    .map(|f| (f.seconds, f.path.to_string_lossy()))
+                    .collect();
+                files.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+                let mut lints: Vec<_> = self.lints.iter().map(|(k, v)| (*v, k.into())).collect();
+                lints.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+                for (title, rows) in [("Proposal", files), ("Lint", lints)] {
+                    writeln!(stderr, "{:>10}  {title}", "Seconds").unwrap();
+                    for (seconds, name) in rows {
+                        writeln!(stderr, "{seconds:>10.3}  {name}").unwrap();
+                    }
+                    writeln!(stderr).unwrap();
+                }
+            }
+        }
+    }
+}
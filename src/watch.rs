@@ -0,0 +1,333 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Live reload support for `serve`.
+//!
+//! Edits to proposals in the root checkout are mirrored into the build
+//! repository and re-preprocessed, so zola's own file watcher picks them up.
+
+use std::{
+    collections::BTreeSet,
+    io::ErrorKind,
+    path::{Component, Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use log::{debug, error, info, warn};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+use snafu::{Backtrace, Report, ResultExt, Snafu, Whatever};
+use walkdir::WalkDir;
+
+use crate::{
+    git::LastModified,
+    layout::{CONTENT_DIR, OVERRIDES_FILE},
+    lint, markdown,
+};
+
+/// How long to wait for more events before syncing what changed.
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Longest to keep waiting for more events, so a steady stream of them can't
+/// hold off syncing forever.
+const MAX_BATCH: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("unable to watch `{}`", path.to_string_lossy()))]
+    Notify {
+        path: PathBuf,
+        #[snafu(source(from(notify::Error, Box::new)))]
+        source: Box<notify::Error>,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("unable to start watcher thread"))]
+    Spawn {
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+}
+
+#[derive(Debug)]
+struct Sync {
+    root_path: PathBuf,
+    repo_path: PathBuf,
+
+    lint_settings: lint::Settings,
+    eipw: lint::CmdArgs,
+    last_modified: LastModified,
+}
+
+/// Watches the root checkout for as long as it is alive.
+pub struct Watcher {
+    _watcher: RecommendedWatcher,
+    _thread: JoinHandle<()>,
+}
+
+impl Watcher {
+    pub fn spawn(
+        lint_settings: &lint::Settings,
+        root_path: &Path,
+        repo_path: &Path,
+        eipw: lint::CmdArgs,
+        last_modified: LastModified,
+    ) -> Result<Self, Error> {
+        let (tx, rx) = mpsc::channel();
+
+        let content_path = root_path.join(CONTENT_DIR);
+        let mut watcher = notify::recommended_watcher(tx).context(NotifySnafu {
+            path: &content_path,
+        })?;
+        watcher
+            .watch(&content_path, RecursiveMode::Recursive)
+            .context(NotifySnafu {
+                path: &content_path,
+            })?;
+
+        let sync = Sync {
+            root_path: root_path.to_owned(),
+            repo_path: repo_path.to_owned(),
+            lint_settings: lint_settings.clone(),
+            eipw: eipw.for_reload(),
+            last_modified,
+        };
+
+        let thread = std::thread::Builder::new()
+            .name("watch".into())
+            .spawn(move || sync.run(rx))
+            .context(SpawnSnafu)?;
+
+        info!("watching `{}` for changes", content_path.to_string_lossy());
+
+        Ok(Self {
+            _watcher: watcher,
+            _thread: thread,
+        })
+    }
+}
+
+/// Name of the top-level content entry (`00001.md` or `00001`) containing
+/// `path`, if it is a proposal.
+///
+/// Proposals are `NNNNN.md` files or `NNNNN/` directories with an `index.md`,
+/// either in the root checkout or (if they were just removed) in the build
+/// repository's `repo_content`. Anything else, like an editor's temporary
+/// files, is ignored.
+fn proposal_entry(content_path: &Path, repo_content: &Path, path: &Path) -> Option<PathBuf> {
+    let relative = path.strip_prefix(content_path).ok()?;
+    let entry = match relative.components().next()? {
+        Component::Normal(n) => Path::new(n),
+        _ => return None,
+    };
+
+    let (name, proposal) = match entry.extension() {
+        None => (entry.as_os_str(), entry.join("index.md")),
+        Some(e) if e == "md" => (entry.file_stem()?, entry.to_path_buf()),
+        Some(_) => return None,
+    };
+
+    let name = name.to_str()?;
+    if name.len() != 5 || !name.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    [content_path, repo_content]
+        .iter()
+        .any(|c| c.join(&proposal).is_file())
+        .then(|| entry.to_path_buf())
+}
+
+/// Whether `relative` (to a proposal directory) is markdown that preprocessing
+/// writes, or lint overrides it leaves out, instead of a file to copy as-is.
+fn is_preprocessed(relative: &Path) -> bool {
+    relative == Path::new("index.md")
+        || relative == Path::new(OVERRIDES_FILE)
+        || (relative.starts_with("assets") && relative.extension().is_some_and(|e| e == "md"))
+}
+
+/// Copy `from` over `to` through a temporary file, so zola never sees a
+/// partial copy.
+fn copy_atomic(from: &Path, to: &Path) -> std::io::Result<()> {
+    // Zola ignores `.tmp` files.
+    let mut temp = to.as_os_str().to_owned();
+    temp.push(".tmp");
+
+    std::fs::copy(from, &temp)?;
+    std::fs::rename(&temp, to)
+}
+
+/// Make the directory `to` match `from`, except for the markdown files that
+/// preprocessing writes.
+fn sync_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    for entry in WalkDir::new(from) {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(from).unwrap();
+        let dest = to.join(relative);
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&dest)?;
+        } else if !is_preprocessed(relative) {
+            copy_atomic(entry.path(), &dest)?;
+        }
+    }
+
+    // Remove whatever was deleted from `from`, deepest entries first.
+    for entry in WalkDir::new(to).contents_first(true) {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(to).unwrap();
+        if !from.join(relative).exists() {
+            remove(entry.path())?;
+        }
+    }
+
+    Ok(())
+}
+
+fn remove(path: &Path) -> std::io::Result<()> {
+    let result = match std::fs::symlink_metadata(path) {
+        Ok(m) if m.is_dir() => std::fs::remove_dir_all(path),
+        Ok(_) => std::fs::remove_file(path),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        other => other,
+    }
+}
+
+impl Sync {
+    fn run(self, rx: Receiver<notify::Result<Event>>) {
+        let content_path = self.root_path.join(CONTENT_DIR);
+        let repo_content = self.repo_path.join(CONTENT_DIR);
+
+        while let Ok(first) = rx.recv() {
+            let mut entries = BTreeSet::new();
+            let mut next = Some(first);
+            let deadline = Instant::now() + MAX_BATCH;
+
+            while let Some(result) = next.take() {
+                match result {
+                    Ok(event) if matches!(event.kind, EventKind::Access(_)) => (),
+                    Ok(event) => entries.extend(
+                        event
+                            .paths
+                            .iter()
+                            .filter_map(|p| proposal_entry(&content_path, &repo_content, p)),
+                    ),
+                    Err(e) => warn!("file watcher error: {}", Report::from_error(e)),
+                }
+
+                let timeout = DEBOUNCE.min(deadline.saturating_duration_since(Instant::now()));
+                next = match rx.recv_timeout(timeout) {
+                    Ok(r) => Some(r),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                };
+            }
+
+            for entry in entries {
+                info!("reloading `{}`", entry.to_string_lossy());
+                if let Err(e) = self.reload(&entry) {
+                    error!("{}", Report::from_error(e));
+                }
+            }
+        }
+    }
+
+    fn reload(&self, entry: &Path) -> Result<(), Whatever> {
+        let repo_content = self.repo_path.join(CONTENT_DIR);
+        let source = self.root_path.join(CONTENT_DIR).join(entry);
+        let dest = repo_content.join(entry);
+
+        let metadata = match std::fs::metadata(&source) {
+            Ok(m) => m,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                debug!("`{}` was removed", source.to_string_lossy());
+                return remove(&dest).with_whatever_context(|_| {
+                    format!("unable to remove `{}`", dest.to_string_lossy())
+                });
+            }
+            Err(e) => {
+                return Err(e).with_whatever_context(|_| {
+                    format!("unable to access `{}`", source.to_string_lossy())
+                })
+            }
+        };
+
+        // Lint the root checkout's copy, since the build repository's copy is
+        // only replaced once it has been preprocessed. Edits aren't compared
+        // against upstream, so `--changed-lines` doesn't apply here.
+        let lint_path = if metadata.is_dir() {
+            source.join("index.md")
+        } else {
+            source.clone()
+        };
+        let output = lint::Output::new(&self.eipw, &self.root_path)
+            .whatever_context("unable to set up output")?;
+        let result = lint::eipw(
+            &self.lint_settings,
+            &self.root_path,
+            &self.root_path,
+            vec![lint_path],
+            None,
+            self.eipw.clone(),
+            &output,
+        );
+        output.finish();
+
+        if let Err(e) = result {
+            error!("{}", Report::from_error(e));
+        }
+
+        if metadata.is_dir() {
+            sync_dir(&source, &dest).with_whatever_context(|_| {
+                format!(
+                    "unable to copy `{}` to `{}`",
+                    source.to_string_lossy(),
+                    dest.to_string_lossy()
+                )
+            })?;
+        }
+
+        // Preprocessed files replace the old ones in a single rename, so zola
+        // never sees them missing, or before they've been preprocessed.
+        markdown::preprocess_entry(&repo_content, &source, &dest, &self.last_modified, None)
+            .whatever_context("unable to preprocess markdown")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sync_dir_skips_preprocessed_markdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("from/00001");
+        let to = dir.path().join("to/00001");
+        std::fs::create_dir_all(from.join("assets")).unwrap();
+        std::fs::create_dir_all(to.join("assets/old")).unwrap();
+
+        std::fs::write(from.join("index.md"), "raw").unwrap();
+        std::fs::write(from.join("eipw.toml"), "").unwrap();
+        std::fs::write(from.join("assets/notes.md"), "raw").unwrap();
+        std::fs::write(from.join("assets/x.png"), "new").unwrap();
+
+        std::fs::write(to.join("index.md"), "preprocessed").unwrap();
+        std::fs::write(to.join("assets/x.png"), "old").unwrap();
+        std::fs::write(to.join("assets/old/y.png"), "old").unwrap();
+
+        sync_dir(&from, &to).unwrap();
+
+        let read = |p: &str| std::fs::read_to_string(to.join(p)).unwrap();
+        assert_eq!(read("index.md"), "preprocessed");
+        assert_eq!(read("assets/x.png"), "new");
+        assert!(!to.join("assets/notes.md").exists());
+        assert!(!to.join("eipw.toml").exists());
+        assert!(!to.join("assets/old").exists());
+    }
+}
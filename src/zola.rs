@@ -8,14 +8,19 @@ use std::{
     ffi::OsString,
     io::{BufRead, BufReader, ErrorKind},
     path::{Path, PathBuf},
+    process::ExitStatus,
+    sync::Arc,
 };
 
+use lazy_static::lazy_static;
 use log::{debug, error, info, warn};
+use regex::Regex;
 use semver::Version;
+use serde::Serialize;
 use snafu::{ensure, Backtrace, IntoError, Report, ResultExt, Snafu};
 use url::Url;
 
-use crate::{cache::Cache, git};
+use crate::{cache::Cache, git, interrupt};
 
 const MINIMUM_VERSION: Version = Version::new(0, 22, 1);
 
@@ -76,6 +81,180 @@ pub enum Error {
         #[snafu(backtrace)]
         source: git::Error,
     },
+    #[snafu(display("zola exited unsuccessfully ({status})"))]
+    Exit {
+        status: ExitStatus,
+        backtrace: Backtrace,
+    },
+    #[snafu(context(false))]
+    Interrupt {
+        #[snafu(backtrace)]
+        source: interrupt::Error,
+    },
+    #[snafu(display("zola reported {n_errors} errors :("))]
+    Failed {
+        n_errors: usize,
+        backtrace: Backtrace,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem reported by zola.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+
+    /// Markdown file the problem was found in, if zola mentioned one.
+    pub file: Option<PathBuf>,
+
+    pub message: String,
+}
+
+lazy_static! {
+    // Matches paths to markdown files, possibly quoted.
+    static ref RE_MD_PATH: Regex = Regex::new(r#"([^\s`'"]+\.md)\b"#).unwrap();
+}
+
+/// Turns zola's output into [`Diagnostic`]s.
+///
+/// Zola prefixes the first lines of each problem with `Error: ` or
+/// `Warning: ` (one for the problem, and one for each reason.) Lines following
+/// those (until the next blank line) continue the same problem, and usually
+/// list one broken link each.
+///
+/// Each continuation line that mentions a file becomes its own diagnostic.
+/// Everything else in a problem is combined into one diagnostic, unless the
+/// continuation lines already located every part of it.
+#[derive(Debug)]
+struct Diagnostics<'a> {
+    project_path: &'a Path,
+    problem: Option<Problem>,
+    diagnostics: Vec<Diagnostic>,
+    n_completed: usize,
+}
+
+#[derive(Debug)]
+struct Problem {
+    severity: Severity,
+    reasons: Vec<String>,
+    details: Vec<String>,
+}
+
+impl<'a> Diagnostics<'a> {
+    fn new(project_path: &'a Path) -> Self {
+        Self {
+            project_path,
+            problem: None,
+            diagnostics: Vec::new(),
+            n_completed: 0,
+        }
+    }
+
+    fn file(&self, message: &str) -> Option<PathBuf> {
+        let path = RE_MD_PATH
+            .captures_iter(message)
+            .map(|c| c.get(1).unwrap().as_str())
+            .find(|p| !p.starts_with('@'))?;
+
+        Some(self.project_path.join(path))
+    }
+
+    /// Parse one line of output, returning its severity and message if it is
+    /// part of a problem.
+    fn line<'l>(&mut self, line: &'l str) -> Option<(Severity, &'l str)> {
+        let trimmed = line.trim();
+
+        let prefixed = if trimmed.is_empty() {
+            self.end();
+            return None;
+        } else if let Some(warning) = trimmed.strip_prefix("Warning: ") {
+            Some((Severity::Warning, warning))
+        } else {
+            trimmed
+                .strip_prefix("Error: ")
+                .map(|error| (Severity::Error, error))
+        };
+
+        match (prefixed, &mut self.problem) {
+            (Some((severity, message)), Some(problem))
+                if problem.severity == severity && problem.details.is_empty() =>
+            {
+                let message = message.trim_start_matches("Reason: ");
+                problem.reasons.push(message.to_owned());
+                Some((severity, message))
+            }
+            (Some((severity, message)), _) => {
+                self.end();
+                let message = message.trim_start_matches("Reason: ");
+                self.problem = Some(Problem {
+                    severity,
+                    reasons: vec![message.to_owned()],
+                    details: Vec::new(),
+                });
+                Some((severity, message))
+            }
+            (None, Some(problem)) => {
+                problem.details.push(trimmed.to_owned());
+                Some((problem.severity, trimmed))
+            }
+            (None, None) => None,
+        }
+    }
+
+    /// Turn the problem being parsed (if any) into diagnostics.
+    fn end(&mut self) {
+        let Some(problem) = self.problem.take() else {
+            return;
+        };
+
+        let mut located = Vec::new();
+        let mut rest = Vec::new();
+        for detail in problem.details {
+            match self.file(&detail) {
+                Some(file) => located.push(Diagnostic {
+                    severity: problem.severity,
+                    file: Some(file),
+                    message: detail,
+                }),
+                None => rest.push(detail),
+            }
+        }
+
+        if located.is_empty() || !rest.is_empty() {
+            let reasons = problem.reasons.join(": ");
+            let file = self.file(&reasons);
+            let message = std::iter::once(reasons)
+                .chain(rest)
+                .collect::<Vec<_>>()
+                .join("\n");
+            self.diagnostics.push(Diagnostic {
+                severity: problem.severity,
+                file,
+                message,
+            });
+        }
+
+        self.diagnostics.extend(located);
+    }
+
+    /// Diagnostics completed since the last call.
+    fn completed(&mut self) -> &[Diagnostic] {
+        let start = std::mem::replace(&mut self.n_completed, self.diagnostics.len());
+        &self.diagnostics[start..]
+    }
+
+    fn n_errors(&self) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .count()
+    }
 }
 
 pub fn find_zola() -> Result<(), Error> {
@@ -101,9 +280,17 @@ pub fn check(
     theme_rev: &str,
     cache: &Cache,
     project_path: &Path,
+    on_diagnostic: &mut dyn FnMut(&Diagnostic),
 ) -> Result<(), Error> {
     let args = ["check", "--drafts", "--skip-external-links"];
-    spawn_log(theme_repo, theme_rev, cache, project_path, args)?;
+    spawn_log(
+        theme_repo,
+        theme_rev,
+        cache,
+        project_path,
+        args,
+        on_diagnostic,
+    )?;
     Ok(())
 }
 
@@ -114,13 +301,21 @@ pub fn build(
     project_path: &Path,
     output_path: &Path,
     base_url: &str,
+    on_diagnostic: &mut dyn FnMut(&Diagnostic),
 ) -> Result<(), Error> {
     remove_output(output_path);
     let args = ["build", "--drafts", "-u", base_url, "-o"]
         .map(OsString::from)
         .into_iter()
         .chain(std::iter::once(output_path.into()));
-    spawn_log(theme_repo, theme_rev, cache, project_path, args)?;
+    spawn_log(
+        theme_repo,
+        theme_rev,
+        cache,
+        project_path,
+        args,
+        on_diagnostic,
+    )?;
     if let Ok(url) = Url::from_file_path(output_path) {
         info!("HTML output to: {}", url);
     }
@@ -133,15 +328,21 @@ pub fn serve(
     cache: &Cache,
     project_path: &Path,
     output_path: &Path,
+    on_diagnostic: &mut dyn FnMut(&Diagnostic),
 ) -> Result<(), Error> {
-    // TODO: Properly kill the child process when we receive ctrl-c.
-    warn!("live reloading is not implemented");
     remove_output(output_path);
     let args = ["serve", "--drafts", "-o"]
         .map(OsString::from)
         .into_iter()
         .chain(std::iter::once(output_path.into()));
-    spawn_log(theme_repo, theme_rev, cache, project_path, args)?;
+    spawn_log(
+        theme_repo,
+        theme_rev,
+        cache,
+        project_path,
+        args,
+        on_diagnostic,
+    )?;
     Ok(())
 }
 
@@ -160,6 +361,7 @@ fn spawn_log<U, I>(
     cache: &Cache,
     project_path: &Path,
     args: U,
+    on_diagnostic: &mut dyn FnMut(&Diagnostic),
 ) -> Result<(), Error>
 where
     U: IntoIterator<Item = I>,
@@ -192,32 +394,147 @@ where
         .dir(project_path)
         .stdin_null()
         .stderr_to_stdout()
+        .unchecked()
         .reader()
         .context(IoSnafu)?;
+    let reader = Arc::new(reader);
+    let guard = interrupt::register(reader.clone())?;
+    // An interrupt that arrived before registering wouldn't have killed zola.
+    interrupt::check()?;
 
-    let mut buf = BufReader::new(reader);
+    let mut buf = BufReader::new(&*reader);
     let mut line = String::new();
+    let mut diagnostics = Diagnostics::new(project_path);
 
     while buf.read_line(&mut line).context(IoSnafu)? > 0 {
-        let trimmed = line.trim();
-        if trimmed.is_empty() {
-            continue;
+        match diagnostics.line(&line) {
+            Some((Severity::Warning, message)) => warn!("{}", message),
+            Some((Severity::Error, message)) => error!("{}", message),
+            None if line.trim().is_empty() => (),
+            None => info!("{}", line.trim()),
         }
 
-        if let Some(warning) = trimmed.strip_prefix("Warning: ") {
-            warn!("{}", warning);
-        } else if let Some(error) = trimmed.strip_prefix("Error: ") {
-            error!("{}", error);
-        } else {
-            info!("{}", trimmed);
-        }
+        diagnostics.completed().iter().for_each(&mut *on_diagnostic);
+
         line.clear();
     }
 
-    buf.into_inner()
+    diagnostics.end();
+    diagnostics.completed().iter().for_each(&mut *on_diagnostic);
+
+    drop(buf);
+    drop(guard);
+
+    let status = reader
         .try_wait()
         .context(IoSnafu)?
-        .expect("zola should have exited");
+        .expect("zola should have exited")
+        .status;
+
+    interrupt::check()?;
+
+    let n_errors = diagnostics.n_errors();
+    ensure!(n_errors == 0, FailedSnafu { n_errors });
+    ensure!(status.success(), ExitSnafu { status });
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{Diagnostic, Diagnostics, Severity};
+
+    fn parse(output: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = Diagnostics::new(Path::new("/project"));
+        for line in output.lines() {
+            diagnostics.line(line);
+        }
+        diagnostics.end();
+        diagnostics.diagnostics
+    }
+
+    #[test]
+    fn informational_lines_are_not_diagnostics() {
+        let output =
+            "Checking site...\n-> Creating 12 pages (0 orphan) and 3 sections\nDone in 1.2s.\n";
+
+        assert!(parse(output).is_empty());
+    }
+
+    #[test]
+    fn continuation_lines_inherit_severity() {
+        let output = concat!(
+            "Error: Failed to check the site\n",
+            "Error: Reason: Found 2 broken internal anchor link(s)\n",
+            "Dead anchor in `/project/content/00001.md` to `@/00002.md#missing`\n",
+            "Dead anchor in `content/00003/index.md` to `@/00002.md#gone`\n",
+            "\n",
+            "Done in 1.2s.\n",
+        );
+
+        let mut diagnostics = Diagnostics::new(Path::new("/project"));
+        for line in output.lines() {
+            diagnostics.line(line);
+        }
+        diagnostics.end();
+
+        assert_eq!(diagnostics.n_errors(), 2);
+        assert_eq!(
+            diagnostics.diagnostics,
+            [
+                Diagnostic {
+                    severity: Severity::Error,
+                    file: Some(PathBuf::from("/project/content/00001.md")),
+                    message: "Dead anchor in `/project/content/00001.md` to `@/00002.md#missing`"
+                        .into(),
+                },
+                Diagnostic {
+                    severity: Severity::Error,
+                    file: Some(PathBuf::from("/project/content/00003/index.md")),
+                    message: "Dead anchor in `content/00003/index.md` to `@/00002.md#gone`".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn unlocated_problems_are_one_diagnostic() {
+        let output = concat!(
+            "Error: Failed to build the site\n",
+            "Error: Reason: Tera error\n",
+            "Template not found\n",
+        );
+
+        let diagnostics = parse(output);
+
+        assert_eq!(
+            diagnostics,
+            [Diagnostic {
+                severity: Severity::Error,
+                file: None,
+                message: "Failed to build the site: Tera error\nTemplate not found".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn warnings_are_not_errors() {
+        let output = "Warning: Highlight language foo not found in content/00004.md\n";
+
+        let mut diagnostics = Diagnostics::new(Path::new("/project"));
+        diagnostics.line(output);
+        diagnostics.end();
+
+        assert_eq!(diagnostics.n_errors(), 0);
+        assert_eq!(
+            diagnostics.diagnostics,
+            [Diagnostic {
+                severity: Severity::Warning,
+                file: Some(PathBuf::from("/project/content/00004.md")),
+                message: "Highlight language foo not found in content/00004.md".into(),
+            }]
+        );
+    }
+}